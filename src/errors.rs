@@ -2,8 +2,10 @@
 
 use std::error::Error;
 use std::fmt;
+use std::io;
+use std::mem;
 
-#[derive(PartialEq, Debug)]
+#[derive(Debug)]
 pub enum SprsError {
     IncompatibleDimensions,
     BadWorkspaceDimensions,
@@ -24,6 +26,15 @@ pub enum SprsError {
     EmptyBlock,
     SingularMatrix,
     NonSquareMatrix,
+    NonSymmetricMatrix,
+    IncompatiblePatterns,
+    /// A structure check failed, the error describes where. This is
+    /// returned by the `_detailed` constructors, such as
+    /// `CsMat::new_owned_detailed`, while the other constructors return
+    /// the plain variant given by `StructureError::kind`.
+    Structure(StructureError),
+    /// An IO operation failed
+    Io(io::Error),
 }
 
 use self::SprsError::*;
@@ -51,6 +62,10 @@ impl SprsError {
             EmptyBlock => "tried to create an empty block",
             SingularMatrix => "matrix is singular",
             NonSquareMatrix => "matrix should be square",
+            NonSymmetricMatrix => "matrix should be symmetric",
+            IncompatiblePatterns => "sparsity patterns do not agree",
+            Structure(ref err) => err.descr(),
+            Io(_) => "an IO operation failed",
         }
     }
 }
@@ -59,10 +74,191 @@ impl Error for SprsError {
     fn description(&self) -> &str {
         self.descr()
     }
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            Structure(ref err) => Some(err),
+            Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for SprsError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.descr().fmt(f)
+        match *self {
+            Structure(ref err) => err.fmt(f),
+            Io(ref err) => write!(f, "{}: {}", self.descr(), err),
+            _ => self.descr().fmt(f),
+        }
+    }
+}
+
+/// IO errors are compared by kind, other errors by value
+impl PartialEq for SprsError {
+    fn eq(&self, other: &SprsError) -> bool {
+        match (self, other) {
+            (Structure(lhs), Structure(rhs)) => lhs == rhs,
+            (Io(lhs), Io(rhs)) => lhs.kind() == rhs.kind(),
+            (Structure(_), _) | (_, Structure(_)) => false,
+            (Io(_), _) | (_, Io(_)) => false,
+            (lhs, rhs) => mem::discriminant(lhs) == mem::discriminant(rhs),
+        }
+    }
+}
+
+impl From<io::Error> for SprsError {
+    fn from(err: io::Error) -> SprsError {
+        Io(err)
+    }
+}
+
+impl From<StructureError> for SprsError {
+    fn from(err: StructureError) -> SprsError {
+        Structure(err)
+    }
+}
+
+/// Detailed description of a failed structure check, locating the
+/// offending values in the compressed storage arrays.
+///
+/// Positions are given as offsets into the `indptr` or `indices` arrays,
+/// and `outer` is the outer dimension containing the offending index
+/// (it is `None` when checking a sparse vector).
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum StructureError {
+    /// indptr should have a length of outer_dims() + 1
+    BadIndptrLength { expected: usize, found: usize },
+    /// indices and data should have the same length
+    DataIndicesMismatch { indices_len: usize, data_len: usize },
+    /// the nnz count should be the length of indices
+    BadNnzCount { expected: usize, found: usize },
+    /// indptr[position] should not exceed the nnz count
+    OutOfBoundsIndptr { position: usize, value: usize, nnz: usize },
+    /// indptr[position] should not be lower than indptr[position - 1]
+    UnsortedIndptr { position: usize, value: usize, previous: usize },
+    /// indices[position] should be greater than indices[position - 1]
+    NonSortedIndices {
+        outer: Option<usize>,
+        position: usize,
+        index: usize,
+        previous: usize,
+    },
+    /// indices[position] should be lower than the inner dimension
+    OutOfBoundsIndex {
+        outer: Option<usize>,
+        position: usize,
+        index: usize,
+        bound: usize,
+    },
+}
+
+impl StructureError {
+    /// The plain error variant corresponding to this structure error
+    pub fn kind(&self) -> SprsError {
+        match *self {
+            StructureError::BadIndptrLength { .. } => BadIndptrLength,
+            StructureError::DataIndicesMismatch { .. } => DataIndicesMismatch,
+            StructureError::BadNnzCount { .. } => BadNnzCount,
+            StructureError::OutOfBoundsIndptr { .. } => OutOfBoundsIndptr,
+            StructureError::UnsortedIndptr { .. } => UnsortedIndptr,
+            StructureError::NonSortedIndices { .. } => NonSortedIndices,
+            StructureError::OutOfBoundsIndex { .. } => OutOfBoundsIndex,
+        }
+    }
+
+    fn descr(&self) -> &str {
+        match *self {
+            StructureError::BadIndptrLength { .. } =>
+                "inpdtr's length doesn't agree with dimensions",
+            StructureError::DataIndicesMismatch { .. } =>
+                "data and indices lengths differ",
+            StructureError::BadNnzCount { .. } =>
+                "the nnz count and indptr do not agree",
+            StructureError::OutOfBoundsIndptr { .. } =>
+                "some indptr values are out of bounds",
+            StructureError::UnsortedIndptr { .. } => "indptr is not sorted",
+            StructureError::NonSortedIndices { .. } =>
+                "a vector's indices are not sorted",
+            StructureError::OutOfBoundsIndex { .. } =>
+                "an element in indices is out of bounds",
+        }
+    }
+}
+
+impl Error for StructureError {
+    fn description(&self) -> &str {
+        self.descr()
+    }
+}
+
+impl fmt::Display for StructureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StructureError::BadIndptrLength { expected, found } =>
+                write!(f, "indptr has length {}, expected {}",
+                       found, expected),
+            StructureError::DataIndicesMismatch { indices_len, data_len } =>
+                write!(f, "indices has length {} but data has length {}",
+                       indices_len, data_len),
+            StructureError::BadNnzCount { expected, found } =>
+                write!(f, "nnz count is {} but indices has length {}",
+                       found, expected),
+            StructureError::OutOfBoundsIndptr { position, value, nnz } =>
+                write!(f, "indptr[{}] = {} exceeds the nnz count {}",
+                       position, value, nnz),
+            StructureError::UnsortedIndptr { position, value, previous } =>
+                write!(f, "indptr is not sorted: indptr[{}] = {} < {}",
+                       position, value, previous),
+            StructureError::NonSortedIndices {
+                outer, position, index, previous
+            } => {
+                write!(f, "indices are not sorted: indices[{}] = {} \
+                           does not follow {}",
+                       position, index, previous)?;
+                match outer {
+                    Some(outer) => write!(f, " in outer dimension {}", outer),
+                    None => Ok(()),
+                }
+            }
+            StructureError::OutOfBoundsIndex {
+                outer, position, index, bound
+            } => {
+                write!(f, "index out of bounds: indices[{}] = {} \
+                           is not lower than {}",
+                       position, index, bound)?;
+                match outer {
+                    Some(outer) => write!(f, " in outer dimension {}", outer),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+    use std::io;
+    use super::{SprsError, StructureError};
+
+    #[test]
+    fn error_sources() {
+        let io_err = io::Error::new(io::ErrorKind::NotFound, "no matrix file");
+        let err = SprsError::from(io_err);
+        assert!(err.source().is_some());
+        assert_eq!(err, SprsError::Io(io::Error::from(io::ErrorKind::NotFound)));
+        assert!(err != SprsError::NotImplemented);
+
+        let structure_err = StructureError::NonSortedIndices {
+            outer: Some(2), position: 5, index: 1, previous: 3,
+        };
+        let err = SprsError::from(structure_err.clone());
+        assert_eq!(err, SprsError::Structure(structure_err));
+        assert!(err != SprsError::NonSortedIndices);
+        assert_eq!(format!("{}", err),
+                   "indices are not sorted: indices[5] = 1 does not follow 3 \
+                    in outer dimension 2");
+        assert!(SprsError::NonSortedIndices.source().is_none());
     }
 }
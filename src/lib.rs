@@ -49,18 +49,22 @@ assert_eq!(a, b.to_csc());
 
 extern crate num;
 extern crate dense_mats;
+#[cfg(feature = "rayon")]
+extern crate rayon;
 
 pub mod sparse;
 pub mod errors;
 pub mod stack;
 
 pub use sparse::{CsMat, CsMatOwned, CsMatView,
-                 CsVec, CsVecView, CsVecOwned};
+                 CsVec, CsVecView, CsVecOwned,
+                 SparseAccumulator};
 pub use sparse::CompressedStorage::{CSR, CSC};
 pub use sparse::construct::{vstack, hstack, bmat};
 
 mod utils {
     use sparse::csmat::{self, CsMatView};
+    use errors::StructureError;
 
     /// Create a borrowed CsMat matrix from sliced data without
     /// checking validity. Intended for internal use only.
@@ -78,6 +82,67 @@ mod utils {
                                data.as_ptr())
         }
     }
+
+    /// Check that the indices of a sparse slice are sorted and lower than
+    /// dim. offset is the position of the slice in the full indices array,
+    /// and is used for error reporting along with the outer dimension.
+    pub fn check_sparse_indices(indices: &[usize],
+                                dim: usize,
+                                outer: Option<usize>,
+                                offset: usize
+                               ) -> Result<(), StructureError> {
+        for (pos, window) in indices.windows(2).enumerate() {
+            if window[0] >= window[1] {
+                return Err(StructureError::NonSortedIndices {
+                    outer,
+                    position: offset + pos + 1,
+                    index: window[1],
+                    previous: window[0],
+                });
+            }
+        }
+        // indices are sorted, so only the last ones can be out of bounds
+        if let Some(pos) = indices.iter().position(|&ind| ind >= dim) {
+            return Err(StructureError::OutOfBoundsIndex {
+                outer,
+                position: offset + pos,
+                index: indices[pos],
+                bound: dim,
+            });
+        }
+        Ok(())
+    }
+
+    /// Sort the (index, value) pairs of a sparse slice by index, merging
+    /// the values of duplicate indices with combine, and append the result
+    /// to out_indices and out_data.
+    ///
+    /// Duplicates are combined in their order of appearance in the input.
+    pub fn sort_and_merge_slice<N, F>(indices: &[usize],
+                                      data: &[N],
+                                      combine: &F,
+                                      out_indices: &mut Vec<usize>,
+                                      out_data: &mut Vec<N>)
+    where N: Copy,
+          F: Fn(N, N) -> N {
+        let mut pairs: Vec<_> = indices.iter().cloned()
+                                       .zip(data.iter().cloned())
+                                       .collect();
+        // the sort is stable, which guarantees duplicates are combined
+        // in their input order
+        pairs.sort_by_key(|&(ind, _)| ind);
+        let start = out_indices.len();
+        for (ind, val) in pairs {
+            if out_indices.len() > start && out_indices.last() == Some(&ind) {
+                let last = out_data.last_mut().expect("same len as indices");
+                *last = combine(*last, val);
+            }
+            else {
+                out_indices.push(ind);
+                out_data.push(val);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,232 @@
+//! Sparse accumulator, the dense workspace used to build sparse vectors
+//!
+//! A sparse accumulator (SPA) stores values in a dense array, along with
+//! the list of the positions it has been written to. This gives constant
+//! time random access when building a sparse vector, while keeping the
+//! cost of gathering and resetting proportional to the number of non zeros.
+
+use std::default::Default;
+
+use num::traits::Num;
+
+use sparse::vec::{CsVec, CsVecOwned, CsVecView};
+use sparse::prod::IndexOrdering;
+
+/// A dense workspace of fixed dimension, accumulating the values of a
+/// sparse vector.
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsVec, SparseAccumulator};
+/// let mut spa = SparseAccumulator::new(6);
+/// let x = CsVec::new_owned(6, vec![1, 4], vec![1., 2.]).unwrap();
+/// let y = CsVec::new_owned(6, vec![0, 4], vec![3., 1.]).unwrap();
+/// spa.axpy(2., x.borrowed());
+/// spa.axpy(1., y.borrowed());
+/// let z = spa.gather();
+/// assert_eq!(z.indices(), &[0, 1, 4]);
+/// assert_eq!(z.data(), &[3., 2., 5.]);
+/// spa.reset();
+/// assert_eq!(spa.nnz(), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SparseAccumulator<N> {
+    values: Vec<N>,
+    occupied: Vec<bool>,
+    pattern: Vec<usize>,
+}
+
+impl<N: Copy> SparseAccumulator<N> {
+
+    /// Create an empty accumulator for vectors of dimension dim
+    pub fn new(dim: usize) -> SparseAccumulator<N>
+    where N: Default {
+        SparseAccumulator::new_filled(dim, N::default())
+    }
+
+    /// Create an empty accumulator for vectors of dimension dim, using
+    /// fill to initialize its dense storage. The fill value is never
+    /// observed, this is useful for scalar types without a default value.
+    pub fn new_filled(dim: usize, fill: N) -> SparseAccumulator<N> {
+        SparseAccumulator {
+            values: vec![fill; dim],
+            occupied: vec![false; dim],
+            pattern: Vec::new(),
+        }
+    }
+
+    /// The dimension of the accumulated vectors
+    pub fn dim(&self) -> usize {
+        self.values.len()
+    }
+
+    /// The number of positions written to since the last reset
+    pub fn nnz(&self) -> usize {
+        self.pattern.len()
+    }
+
+    /// The positions written to since the last reset, in the order of
+    /// their first write, unless a sorted gather occurred.
+    pub fn indices(&self) -> &[usize] {
+        &self.pattern
+    }
+
+    /// The value at position ind, if it has been written to
+    pub fn get(&self, ind: usize) -> Option<N> {
+        if self.occupied[ind] {
+            Some(self.values[ind])
+        }
+        else {
+            None
+        }
+    }
+
+    /// Set the value at position ind, replacing any previous value
+    ///
+    /// # Panics
+    ///
+    /// If ind is not lower than the dimension
+    pub fn scatter(&mut self, ind: usize, val: N) {
+        if !self.occupied[ind] {
+            self.occupied[ind] = true;
+            self.pattern.push(ind);
+        }
+        self.values[ind] = val;
+    }
+
+    /// Combine val with the value at position ind using f, or set it if
+    /// the position has not been written to yet
+    ///
+    /// # Panics
+    ///
+    /// If ind is not lower than the dimension
+    pub fn accumulate_with<F>(&mut self, ind: usize, val: N, f: F)
+    where F: FnOnce(N, N) -> N {
+        if self.occupied[ind] {
+            self.values[ind] = f(self.values[ind], val);
+        }
+        else {
+            self.occupied[ind] = true;
+            self.pattern.push(ind);
+            self.values[ind] = val;
+        }
+    }
+
+    /// Add val to the value at position ind
+    ///
+    /// # Panics
+    ///
+    /// If ind is not lower than the dimension
+    pub fn accumulate(&mut self, ind: usize, val: N)
+    where N: Num {
+        self.accumulate_with(ind, val, |acc, val| acc + val);
+    }
+
+    /// Accumulate alpha times the sparse vector x
+    ///
+    /// # Panics
+    ///
+    /// If the dimension of x differs from the accumulator's
+    pub fn axpy(&mut self, alpha: N, x: CsVecView<N>)
+    where N: Num {
+        assert_eq!(x.dim(), self.dim(), "dimension mismatch");
+        for (ind, val) in x.iter() {
+            self.accumulate(ind, alpha * val);
+        }
+    }
+
+    /// Append the accumulated elements to out_indices and out_data, with
+    /// their indices in the requested ordering. The accumulator is left
+    /// untouched, except for the ordering of its pattern.
+    pub fn gather_into(&mut self,
+                       ordering: IndexOrdering,
+                       out_indices: &mut Vec<usize>,
+                       out_data: &mut Vec<N>) {
+        if ordering == IndexOrdering::Sorted {
+            self.pattern.sort_unstable();
+        }
+        out_indices.extend_from_slice(&self.pattern);
+        out_data.extend(self.pattern.iter().map(|&ind| self.values[ind]));
+    }
+
+    /// Append the accumulated elements whose value satisfies keep to
+    /// out_indices and out_data, with their indices in the requested
+    /// ordering. This is typically used to drop numerical zeros.
+    pub fn gather_filtered_into<F>(&mut self,
+                                   ordering: IndexOrdering,
+                                   mut keep: F,
+                                   out_indices: &mut Vec<usize>,
+                                   out_data: &mut Vec<N>)
+    where F: FnMut(N) -> bool {
+        if ordering == IndexOrdering::Sorted {
+            self.pattern.sort_unstable();
+        }
+        for &ind in &self.pattern {
+            let val = self.values[ind];
+            if keep(val) {
+                out_indices.push(ind);
+                out_data.push(val);
+            }
+        }
+    }
+
+    /// Create a sparse vector holding the accumulated elements.
+    pub fn gather(&mut self) -> CsVecOwned<N> {
+        let mut indices = Vec::with_capacity(self.nnz());
+        let mut data = Vec::with_capacity(self.nnz());
+        self.gather_into(IndexOrdering::Sorted, &mut indices, &mut data);
+        CsVec::new_owned(self.dim(), indices, data)
+            .expect("accumulator pattern is in bounds")
+    }
+
+    /// Forget all the accumulated elements, in time proportional to their
+    /// number
+    pub fn reset(&mut self) {
+        for &ind in &self.pattern {
+            self.occupied[ind] = false;
+        }
+        self.pattern.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SparseAccumulator;
+    use sparse::prod::IndexOrdering;
+    use sparse::vec::CsVec;
+
+    #[test]
+    fn accumulate_gather_reset() {
+        let mut spa = SparseAccumulator::new(5);
+        spa.scatter(3, 1.);
+        spa.accumulate(1, 2.);
+        spa.accumulate(3, 2.);
+        spa.accumulate_with(1, 5., f64::max);
+        assert_eq!(spa.get(3), Some(3.));
+        assert_eq!(spa.get(0), None);
+        assert_eq!(spa.indices(), &[3, 1]);
+
+        let mut indices = vec![7];
+        let mut data = vec![7.];
+        spa.gather_into(IndexOrdering::Unsorted, &mut indices, &mut data);
+        assert_eq!(indices, vec![7, 3, 1]);
+        assert_eq!(data, vec![7., 3., 5.]);
+        let v = spa.gather();
+        assert_eq!(v, CsVec::new_owned(5, vec![1, 3], vec![5., 3.]).unwrap());
+
+        let mut indices = Vec::new();
+        let mut data = Vec::new();
+        spa.gather_filtered_into(IndexOrdering::Sorted, |val| val > 4.,
+                                 &mut indices, &mut data);
+        assert_eq!(indices, vec![1]);
+        assert_eq!(data, vec![5.]);
+
+        spa.reset();
+        assert_eq!(spa.nnz(), 0);
+        assert_eq!(spa.get(3), None);
+        // stale values are not visible after a reset
+        spa.accumulate(3, 1.);
+        assert_eq!(spa.gather().data(), &[1.]);
+    }
+}
@@ -1,6 +1,7 @@
 ///! Sparse matrix addition, subtraction
 
 use std::ops::Deref;
+use std::cmp;
 use sparse::csmat::{CsMat, CsMatOwned, CsMatView, CompressedStorage};
 use num::traits::Num;
 use sparse::vec::NnzEither::{Left, Right, Both};
@@ -242,11 +243,201 @@ where N: Num + Copy, F: Fn(N, N) -> N {
     Ok(res)
 }
 
+/// Generic binary operation on two sparse matrices sharing the same
+/// storage, over the union of their patterns.
+///
+/// Unlike `csmat_binop_same_storage_raw`, no zero is substituted for the
+/// missing elements: `both` is applied to the elements present in both
+/// matrices, `left` to the elements only present in lhs and `right` to the
+/// elements only present in rhs. This makes it possible to implement
+/// operations where zero is not a neutral element, such as the maximum of
+/// signed values, or operations changing the scalar type, such as
+/// comparisons. The results are stored whatever their value.
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CSR};
+/// use sprs::sparse::binop;
+/// let a = CsMat::new_owned(CSR, 1, 3, vec![0, 2], vec![0, 1],
+///                          vec![-1., 2.]).unwrap();
+/// let b = CsMat::new_owned(CSR, 1, 3, vec![0, 2], vec![1, 2],
+///                          vec![3., -4.]).unwrap();
+/// // maximum, where missing elements are implicit zeros
+/// let max = binop::csmat_union_binop(&a, &b, |x: f64, y| x.max(y),
+///                                    |x| x.max(0.), |y| y.max(0.)).unwrap();
+/// assert_eq!(max.data(), &[0., 3., 0.]);
+/// ```
+pub fn csmat_union_binop<N1, N2, R, Mat1, Mat2, FB, FL, FR>(
+    lhs: &Mat1, rhs: &Mat2, both: FB, mut left: FL, mut right: FR
+) -> Result<CsMatOwned<R>, SprsError>
+where N1: Copy,
+      N2: Copy,
+      R: Copy,
+      Mat1: SpMatView<N1>,
+      Mat2: SpMatView<N2>,
+      FB: FnMut(N1, N2) -> R,
+      FL: FnMut(N1) -> R,
+      FR: FnMut(N2) -> R {
+    let lhs = lhs.borrowed();
+    let rhs = rhs.borrowed();
+    let max_nnz = lhs.nb_nonzero() + rhs.nb_nonzero();
+    csmat_pattern_binop(lhs, rhs, max_nnz, both, |lval| Some(left(lval)),
+                        |rval| Some(right(rval)))
+}
+
+/// Generic binary operation on two sparse matrices sharing the same
+/// storage, over the intersection of their patterns.
+///
+/// `both` is applied to the elements present in both matrices, and its
+/// results are stored whatever their value.
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CSR};
+/// use sprs::sparse::binop;
+/// let a = CsMat::new_owned(CSR, 1, 3, vec![0, 2], vec![0, 1],
+///                          vec![1., 2.]).unwrap();
+/// let b = CsMat::new_owned(CSR, 1, 3, vec![0, 2], vec![1, 2],
+///                          vec![4., 4.]).unwrap();
+/// let ratio = binop::csmat_intersection_binop(&a, &b, |x, y| x / y)
+///                  .unwrap();
+/// assert_eq!(ratio.indices(), &[1]);
+/// assert_eq!(ratio.data(), &[0.5]);
+/// ```
+pub fn csmat_intersection_binop<N1, N2, R, Mat1, Mat2, FB>(
+    lhs: &Mat1, rhs: &Mat2, both: FB
+) -> Result<CsMatOwned<R>, SprsError>
+where N1: Copy,
+      N2: Copy,
+      R: Copy,
+      Mat1: SpMatView<N1>,
+      Mat2: SpMatView<N2>,
+      FB: FnMut(N1, N2) -> R {
+    let lhs = lhs.borrowed();
+    let rhs = rhs.borrowed();
+    let max_nnz = cmp::min(lhs.nb_nonzero(), rhs.nb_nonzero());
+    csmat_pattern_binop(lhs, rhs, max_nnz, both, |_| None, |_| None)
+}
+
+/// Generic binary operation on two sparse vectors, over the union of
+/// their patterns. See `csmat_union_binop`.
+pub fn csvec_union_binop<N1, N2, R, FB, FL, FR>(lhs: CsVecView<N1>,
+                                                rhs: CsVecView<N2>,
+                                                mut both: FB,
+                                                mut left: FL,
+                                                mut right: FR
+                                               ) -> Result<CsVecOwned<R>,
+                                                           SprsError>
+where N1: Copy,
+      N2: Copy,
+      R: Copy,
+      FB: FnMut(N1, N2) -> R,
+      FL: FnMut(N1) -> R,
+      FR: FnMut(N2) -> R {
+    if lhs.dim() != rhs.dim() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    let dim = lhs.dim();
+    let mut indices = Vec::with_capacity(lhs.nnz() + rhs.nnz());
+    let mut data = Vec::with_capacity(lhs.nnz() + rhs.nnz());
+    pattern_binop_into(lhs, rhs, &mut both,
+                       &mut |lval| Some(left(lval)),
+                       &mut |rval| Some(right(rval)),
+                       &mut indices, &mut data);
+    Ok(CsVec::new_owned(dim, indices, data).unwrap())
+}
+
+/// Generic binary operation on two sparse vectors, over the intersection
+/// of their patterns. See `csmat_intersection_binop`.
+pub fn csvec_intersection_binop<N1, N2, R, FB>(lhs: CsVecView<N1>,
+                                               rhs: CsVecView<N2>,
+                                               mut both: FB
+                                              ) -> Result<CsVecOwned<R>,
+                                                          SprsError>
+where N1: Copy,
+      N2: Copy,
+      R: Copy,
+      FB: FnMut(N1, N2) -> R {
+    if lhs.dim() != rhs.dim() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    let dim = lhs.dim();
+    let max_nnz = cmp::min(lhs.nnz(), rhs.nnz());
+    let mut indices = Vec::with_capacity(max_nnz);
+    let mut data = Vec::with_capacity(max_nnz);
+    pattern_binop_into(lhs, rhs, &mut both, &mut |_| None, &mut |_| None,
+                       &mut indices, &mut data);
+    Ok(CsVec::new_owned(dim, indices, data).unwrap())
+}
+
+fn csmat_pattern_binop<N1, N2, R, FB, FL, FR>(lhs: CsMatView<N1>,
+                                              rhs: CsMatView<N2>,
+                                              max_nnz: usize,
+                                              mut both: FB,
+                                              mut left: FL,
+                                              mut right: FR
+                                             ) -> Result<CsMatOwned<R>,
+                                                         SprsError>
+where N1: Copy,
+      N2: Copy,
+      R: Copy,
+      FB: FnMut(N1, N2) -> R,
+      FL: FnMut(N1) -> Option<R>,
+      FR: FnMut(N2) -> Option<R> {
+    if lhs.rows() != rhs.rows() || lhs.cols() != rhs.cols() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if lhs.storage() != rhs.storage() {
+        return Err(SprsError::IncompatibleStorages);
+    }
+    let mut indptr = Vec::with_capacity(lhs.outer_dims() + 1);
+    let mut indices = Vec::with_capacity(max_nnz);
+    let mut data = Vec::with_capacity(max_nnz);
+    indptr.push(0);
+    for ((_, lv), (_, rv)) in lhs.outer_iterator().zip(rhs.outer_iterator()) {
+        pattern_binop_into(lv, rv, &mut both, &mut left, &mut right,
+                           &mut indices, &mut data);
+        indptr.push(indices.len());
+    }
+    Ok(CsMat::new_owned(lhs.storage(), lhs.rows(), lhs.cols(),
+                        indptr, indices, data).unwrap())
+}
+
+/// Apply a binary operation on the elements of lhs and rhs, where left
+/// and right return None for the elements that should not be stored
+fn pattern_binop_into<N1, N2, R, FB, FL, FR>(lhs: CsVecView<N1>,
+                                             rhs: CsVecView<N2>,
+                                             both: &mut FB,
+                                             left: &mut FL,
+                                             right: &mut FR,
+                                             out_indices: &mut Vec<usize>,
+                                             out_data: &mut Vec<R>)
+where N1: Copy,
+      N2: Copy,
+      FB: FnMut(N1, N2) -> R,
+      FL: FnMut(N1) -> Option<R>,
+      FR: FnMut(N2) -> Option<R> {
+    for elem in lhs.iter().nnz_or_zip(rhs.iter()) {
+        let (ind, val) = match elem {
+            Both((ind, lval, rval)) => (ind, Some(both(lval, rval))),
+            Left((ind, lval)) => (ind, left(lval)),
+            Right((ind, rval)) => (ind, right(rval)),
+        };
+        if let Some(val) = val {
+            out_indices.push(ind);
+            out_data.push(val);
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use sparse::csmat::{CsMat, CsMatOwned};
     use sparse::vec::CsVec;
     use sparse::CompressedStorage::{CSR};
+    use errors::SprsError;
     use test_data::{mat1, mat2, mat1_times_2, mat_dense1};
     use dense_mats::MatOwned;
 
@@ -360,6 +551,49 @@ mod test {
         assert_eq!(expected_output, res);
     }
 
+    #[test]
+    fn pattern_binops() {
+        let a = mat1();
+        let b = mat2();
+        // with the union and zeros substituted, this is the usual addition
+        let res = super::csmat_union_binop(&a, &b, |x, y| x + y,
+                                           |x| x, |y| y).unwrap();
+        assert_eq!(res, mat1_plus_mat2());
+
+        // elementwise comparison, changing the scalar type
+        let res = super::csmat_intersection_binop(&a, &b, |x, y| x < y)
+            .unwrap();
+        for (row, vec) in res.outer_iterator() {
+            for (col, val) in vec.iter() {
+                let lval = a.at(&(row, col)).unwrap();
+                let rval = b.at(&(row, col)).unwrap();
+                assert_eq!(val, lval < rval);
+            }
+        }
+        let err = super::csmat_intersection_binop(&a, &mat1().to_csc(),
+                                                  |x: f64, y: f64| x * y);
+        assert_eq!(err, Err(SprsError::IncompatibleStorages));
+
+        let vec1 = CsVec::new_owned(8, vec![0, 2, 4, 6], vec![2.; 4]).unwrap();
+        let vec2 = CsVec::new_owned(8, vec![1, 2, 5, 6], vec![4.; 4]).unwrap();
+        let res = super::csvec_intersection_binop(vec1.borrowed(),
+                                                  vec2.borrowed(),
+                                                  |x, y| x / y).unwrap();
+        assert_eq!(res.indices(), &[2, 6]);
+        assert_eq!(res.data(), &[0.5, 0.5]);
+        let res = super::csvec_union_binop(vec1.borrowed(), vec2.borrowed(),
+                                           |x, y| (x, y),
+                                           |x| (x, 0.), |y| (0., y)).unwrap();
+        assert_eq!(res.indices(), &[0, 1, 2, 4, 5, 6]);
+        assert_eq!(res.data()[1], (0., 4.));
+        assert_eq!(res.data()[2], (2., 4.));
+        let vec3 = CsVec::new_owned(7, vec![], Vec::<f64>::new()).unwrap();
+        let err = super::csvec_intersection_binop(vec1.borrowed(),
+                                                  vec3.borrowed(),
+                                                  |x, y| x + y);
+        assert_eq!(err, Err(SprsError::IncompatibleDimensions));
+    }
+
     #[test]
     fn csr_add_dense_rowmaj() {
         let a = MatOwned::zeros([3,3]);
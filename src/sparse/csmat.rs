@@ -13,7 +13,7 @@ use std::default::Default;
 use std::slice::{self, Windows};
 use std::ops::{Deref, DerefMut, Add, Sub, Mul, Range};
 use std::mem;
-use num::traits::Num;
+use num::traits::{Num, Signed, Float};
 
 use dense_mats::{StorageOrder, Tensor, MatOwned};
 
@@ -22,7 +22,9 @@ use sparse::vec::{CsVec, CsVecView};
 use sparse::compressed::SpMatView;
 use sparse::binop;
 use sparse::prod;
-use errors::SprsError;
+use sparse::accumulator::SparseAccumulator;
+use errors::{SprsError, StructureError};
+use utils;
 
 
 pub type CsMatOwned<N> = CsMat<N, Vec<usize>, Vec<usize>, Vec<N>>;
@@ -233,6 +235,27 @@ impl<'a, N:'a + Copy> CsMat<N, &'a [usize], &'a [usize], &'a [N]> {
         m.check_compressed_structure().and(Ok(m))
     }
 
+    /// Create a borrowed CsMat matrix from sliced data, checking their
+    /// validity as `new_borrowed` does, but reporting a failed check as
+    /// `SprsError::Structure`, which locates the offending values.
+    pub fn new_borrowed_detailed(
+        storage: CompressedStorage, nrows : usize, ncols: usize,
+        indptr : &'a[usize], indices : &'a[usize], data : &'a[N]
+        )
+    -> Result<CsMatView<'a, N>, SprsError> {
+        let m = CsMat {
+            storage,
+            nrows,
+            ncols,
+            nnz : data.len(),
+            indptr,
+            indices,
+            data,
+        };
+        m.check_compressed_structure_detailed()?;
+        Ok(m)
+    }
+
     /// Create a borrowed CsMat matrix from raw data,
     /// without checking their validity
     ///
@@ -363,6 +386,116 @@ impl<N: Copy> CsMat<N, Vec<usize>, Vec<usize>, Vec<N>> {
         m.check_compressed_structure().and(Ok(m))
     }
 
+    /// Create an owned CsMat matrix from moved data, checking their
+    /// validity as `new_owned` does, but reporting a failed check as
+    /// `SprsError::Structure`, which locates the offending values.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use sprs::{CsMat, CSR};
+    /// use sprs::errors::{SprsError, StructureError};
+    /// let err = CsMat::new_owned_detailed(CSR, 2, 3, vec![0, 2, 4],
+    ///                                     vec![0, 1, 2, 1],
+    ///                                     vec![1., 1., 1., 1.]).unwrap_err();
+    /// assert_eq!(err, SprsError::Structure(StructureError::NonSortedIndices {
+    ///     outer: Some(1), position: 3, index: 1, previous: 2
+    /// }));
+    /// ```
+    pub fn new_owned_detailed(
+        storage: CompressedStorage, nrows : usize, ncols: usize,
+        indptr : Vec<usize>, indices : Vec<usize>, data : Vec<N>
+        )
+    -> Result<CsMatOwned<N>, SprsError> {
+        let m = CsMat {
+            storage,
+            nrows,
+            ncols,
+            nnz : data.len(),
+            indptr,
+            indices,
+            data,
+        };
+        m.check_compressed_structure_detailed()?;
+        Ok(m)
+    }
+
+    /// Create an owned CsMat matrix from moved data, sorting the indices
+    /// of each outer slice and merging duplicate entries.
+    ///
+    /// The values of duplicate entries are merged using `combine`, in their
+    /// order of appearance in `data`. The resulting structure is then checked
+    /// as in `new_owned`.
+    ///
+    /// # Errors
+    ///
+    /// - `BadIndptrLength` if indptr's length is not the outer dimension + 1
+    /// - `DataIndicesMismatch` if indices and data have different lengths
+    /// - `UnsortedIndptr` if indptr does not start at 0 or is decreasing
+    /// - `OutOfBoundsIndptr` if the last indptr value is not the nnz count
+    /// - `OutOfBoundsIndex` if an index exceeds the inner dimension
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use sprs::{CsMat, CSR};
+    /// let a = CsMat::new_owned_unsorted(CSR, 2, 3,
+    ///                                   vec![0, 3, 4],
+    ///                                   vec![2, 0, 2, 1],
+    ///                                   vec![1., 2., 3., 4.],
+    ///                                   |x, y| x + y).unwrap();
+    /// assert_eq!(a.indptr(), &[0, 2, 3]);
+    /// assert_eq!(a.indices(), &[0, 2, 1]);
+    /// assert_eq!(a.data(), &[2., 4., 4.]);
+    /// ```
+    pub fn new_owned_unsorted<F>(
+        storage: CompressedStorage, nrows : usize, ncols: usize,
+        indptr : Vec<usize>, indices : Vec<usize>, data : Vec<N>,
+        combine: F
+        )
+    -> Result<CsMatOwned<N>, SprsError>
+    where F: Fn(N, N) -> N {
+        let outer = match storage {
+            CSR => nrows,
+            CSC => ncols,
+        };
+        // the indptr structure has to be sound before we can sort
+        // each outer slice
+        if indptr.len() != outer + 1 {
+            return Err(SprsError::BadIndptrLength);
+        }
+        if indices.len() != data.len() {
+            return Err(SprsError::DataIndicesMismatch);
+        }
+        // an owned matrix uses all of its data, entries before indptr[0]
+        // would be silently dropped
+        if indptr[0] != 0 {
+            return Err(SprsError::UnsortedIndptr);
+        }
+        if ! indptr.windows(2).all(|x| x[0] <= x[1]) {
+            return Err(SprsError::UnsortedIndptr);
+        }
+        if indptr[outer] != indices.len() {
+            return Err(SprsError::OutOfBoundsIndptr);
+        }
+
+        let mut out_indptr = Vec::with_capacity(outer + 1);
+        let mut out_indices = Vec::with_capacity(indices.len());
+        let mut out_data = Vec::with_capacity(data.len());
+        out_indptr.push(0);
+        for window in indptr.windows(2) {
+            let range = window[0]..window[1];
+            utils::sort_and_merge_slice(&indices[range.clone()],
+                                        &data[range],
+                                        &combine,
+                                        &mut out_indices,
+                                        &mut out_data);
+            out_indptr.push(out_indices.len());
+        }
+        CsMat::new_owned(storage, nrows, ncols,
+                         out_indptr, out_indices, out_data)
+    }
+
     /// Append an outer dim to an existing matrix, compressing it in the process
     pub fn append_outer(mut self, data: &[N]) -> Self where N: Num {
         for (inner_ind, val) in data.iter().enumerate() {
@@ -462,13 +595,15 @@ where N: Copy,
 
     /// Return an outer iterator over P*A, as well as the proper permutation
     /// for iterating over the inner dimension of P*A*P^T
+    ///
+    /// The k-th item is the outer vector `perm[k]`, whatever the storage.
     /// Unstable
     pub fn outer_iterator_perm<'a, 'perm: 'a>(
         &'a self, perm: PermView<'perm>)
     -> OuterIteratorPerm<'a, 'perm, N> {
-        let (inner_len, oriented_perm) = match self.storage {
-            CSR => (self.ncols, perm.reborrow()),
-            CSC => (self.nrows, perm.reborrow_inv())
+        let inner_len = match self.storage {
+            CSR => self.ncols,
+            CSC => self.nrows,
         };
         let n = self.indptr.len() - 1;
         OuterIteratorPerm {
@@ -477,7 +612,7 @@ where N: Copy,
             indptr: &self.indptr[..],
             indices: &self.indices[..],
             data: &self.data[..],
-            perm: oriented_perm
+            perm: perm.reborrow()
         }
     }
 
@@ -660,6 +795,74 @@ where N: Copy,
         }
     }
 
+    /// Create a new matrix with the same sparsity pattern, whose values
+    /// are obtained by applying f to the values of this matrix.
+    ///
+    /// The values are not filtered, so a value mapped to zero is still
+    /// explicitly stored.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use sprs::{CsMat, CSR};
+    /// let a = CsMat::new_owned(CSR, 2, 2, vec![0, 1, 2], vec![0, 1],
+    ///                          vec![-1.5f64, 2.]).unwrap();
+    /// let b = a.map(|val| val.abs() as f32);
+    /// assert_eq!(b.data(), &[1.5f32, 2.]);
+    /// ```
+    pub fn map<M, F>(&self, f: F) -> CsMatOwned<M>
+    where F: FnMut(N) -> M {
+        CsMatOwned {
+            storage: self.storage,
+            nrows: self.nrows,
+            ncols: self.ncols,
+            nnz: self.nnz,
+            indptr: self.indptr.to_vec(),
+            indices: self.indices.to_vec(),
+            data: self.data.iter().cloned().map(f).collect(),
+        }
+    }
+
+    /// Create a new matrix by combining the values of this matrix
+    /// and of other, which must have the same sparsity pattern.
+    ///
+    /// # Errors
+    ///
+    /// - `IncompatibleDimensions` if the shapes differ
+    /// - `IncompatibleStorages` if the storages differ
+    /// - `IncompatiblePatterns` if the sparsity patterns differ
+    pub fn zip_map<M, R, IpS2, IS2, DS2, F>(&self,
+                                            other: &CsMat<M, IpS2, IS2, DS2>,
+                                            mut f: F
+                                           ) -> Result<CsMatOwned<R>,
+                                                       SprsError>
+    where M: Copy,
+          IpS2: Deref<Target=[usize]>,
+          IS2: Deref<Target=[usize]>,
+          DS2: Deref<Target=[M]>,
+          F: FnMut(N, M) -> R {
+        if self.rows() != other.rows() || self.cols() != other.cols() {
+            return Err(SprsError::IncompatibleDimensions);
+        }
+        if self.storage != other.storage() {
+            return Err(SprsError::IncompatibleStorages);
+        }
+        if self.indptr() != other.indptr() || self.indices() != other.indices() {
+            return Err(SprsError::IncompatiblePatterns);
+        }
+        Ok(CsMatOwned {
+            storage: self.storage,
+            nrows: self.nrows,
+            ncols: self.ncols,
+            nnz: self.nnz,
+            indptr: self.indptr.to_vec(),
+            indices: self.indices.to_vec(),
+            data: self.data.iter().zip(other.data().iter())
+                           .map(|(&lhs, &rhs)| f(lhs, rhs))
+                           .collect(),
+        })
+    }
+
     /// Access an element given its outer_ind and inner_ind.
     /// Will return None if there is no non-zero element at this location.
     ///
@@ -682,41 +885,48 @@ where N: Copy,
     ///   indices and indptr would take more space than the addressable memory
     /// * indices is sorted for each outer slice
     /// * indices are lower than inner_dims()
+    ///
+    /// See `check_compressed_structure_detailed` to get the location
+    /// of the offending values.
     pub fn check_compressed_structure(&self) -> Result<(), SprsError> {
-        let outer = self.outer_dims();
+        self.check_compressed_structure_detailed().map_err(|e| e.kind())
+    }
 
-        if self.indptr.len() != outer + 1 {
-            return Err(SprsError::BadIndptrLength);
-        }
-        if self.indices.len() != self.data.len() {
-            return Err(SprsError::DataIndicesMismatch);
-        }
+    /// Check the structure of CsMat components, as
+    /// `check_compressed_structure` does, but describe the location
+    /// of the first problem found.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use sprs::{CsMat, CSR};
+    /// use sprs::errors::StructureError;
+    /// let indptr = [0, 2, 4];
+    /// let indices = [0, 1, 2, 1];
+    /// let data = [1., 1., 1., 1.];
+    /// let a = unsafe {
+    ///     CsMat::new_raw(CSR, 2, 3, indptr.as_ptr(),
+    ///                    indices.as_ptr(), data.as_ptr())
+    /// };
+    /// let err = a.check_compressed_structure_detailed().unwrap_err();
+    /// assert_eq!(err, StructureError::NonSortedIndices {
+    ///     outer: Some(1), position: 3, index: 1, previous: 2
+    /// });
+    /// ```
+    pub fn check_compressed_structure_detailed(&self)
+    -> Result<(), StructureError> {
         let nnz = self.indices.len();
-        if nnz != self.nnz {
-            return Err(SprsError::BadNnzCount);
-        }
-        if let Some(&max_indptr) = self.indptr.iter().max() {
-            if max_indptr > nnz {
-                return Err(SprsError::OutOfBoundsIndptr);
-            }
-            if max_indptr > usize::max_value() / 2 {
-                return Err(SprsError::OutOfBoundsIndptr);
-            }
+        if self.indptr.len() == self.outer_dims() + 1
+           && self.indices.len() == self.data.len()
+           && nnz != self.nnz {
+            return Err(StructureError::BadNnzCount {
+                expected: nnz,
+                found: self.nnz,
+            });
         }
-        else {
-            unreachable!();
-        }
-
-        if ! self.indptr.deref().windows(2).all(|x| x[0] <= x[1]) {
-            return Err(SprsError::UnsortedIndptr);
-        }
-
-        // check that the indices are sorted for each row
-        for (_, vec) in self.outer_iterator() {
-            try!(vec.check_structure());
-        }
-
-        Ok(())
+        check_compressed_structure_raw(self.storage, self.nrows, self.ncols,
+                                       &self.indptr, &self.indices,
+                                       &self.data)
     }
 
     /// Return a view into the current matrix
@@ -731,6 +941,141 @@ where N: Copy,
             data: &self.data[..],
         }
     }
+
+    /// Compute the product of this matrix with the dense vector in_vec,
+    /// storing it into res_vec. Both storages are handled.
+    ///
+    /// # Errors
+    ///
+    /// - `IncompatibleDimensions` if the length of in_vec does not match
+    ///   the number of columns, or the length of res_vec does not match
+    ///   the number of rows
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use sprs::CsMat;
+    /// let a = CsMat::new_owned(sprs::CSR, 2, 2, vec![0, 1, 2], vec![1, 0],
+    ///                          vec![2., 3.]).unwrap();
+    /// let mut res = vec![0.; 2];
+    /// a.mul_dense_vec(&[1., 2.], &mut res).unwrap();
+    /// assert_eq!(res, vec![4., 3.]);
+    /// ```
+    pub fn mul_dense_vec(&self,
+                         in_vec: &[N],
+                         res_vec: &mut [N]) -> Result<(), SprsError>
+    where N: Num {
+        prod::spmv(N::one(), self, prod::Transpose::No, in_vec, N::zero(),
+                   res_vec)
+    }
+
+    /// Reduce each outer dimension of the matrix using f, starting from init
+    fn fold_outer<M, F>(&self, init: M, mut f: F) -> Vec<M>
+    where M: Copy,
+          F: FnMut(M, N) -> M {
+        self.outer_iterator()
+            .map(|(_, vec)| vec.iter().fold(init, |acc, (_, val)| f(acc, val)))
+            .collect()
+    }
+
+    /// Reduce each inner dimension of the matrix using f, starting from init
+    fn fold_inner<M, F>(&self, init: M, mut f: F) -> Vec<M>
+    where M: Copy,
+          F: FnMut(M, N) -> M {
+        let mut res = vec![init; self.inner_dims()];
+        for (_, vec) in self.outer_iterator() {
+            for (ind, val) in vec.iter() {
+                res[ind] = f(res[ind], val);
+            }
+        }
+        res
+    }
+
+    fn fold_rows<M, F>(&self, init: M, f: F) -> Vec<M>
+    where M: Copy,
+          F: FnMut(M, N) -> M {
+        match self.storage {
+            CSR => self.fold_outer(init, f),
+            CSC => self.fold_inner(init, f),
+        }
+    }
+
+    fn fold_cols<M, F>(&self, init: M, f: F) -> Vec<M>
+    where M: Copy,
+          F: FnMut(M, N) -> M {
+        match self.storage {
+            CSR => self.fold_inner(init, f),
+            CSC => self.fold_outer(init, f),
+        }
+    }
+
+    /// Sum of the elements of each outer dimension, eg the row sums
+    /// of a CSR matrix
+    pub fn sum_outer(&self) -> Vec<N> where N: Num {
+        self.fold_outer(N::zero(), |acc, val| acc + val)
+    }
+
+    /// Sum of the elements of each inner dimension, eg the column sums
+    /// of a CSR matrix
+    pub fn sum_inner(&self) -> Vec<N> where N: Num {
+        self.fold_inner(N::zero(), |acc, val| acc + val)
+    }
+
+    /// Sum of the elements of each row, for any storage
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use sprs::{CsMat, CSC};
+    /// let a = CsMat::new_owned(CSC, 2, 2, vec![0, 2, 3], vec![0, 1, 1],
+    ///                          vec![1., 2., 3.]).unwrap();
+    /// assert_eq!(a.row_sums(), vec![1., 5.]);
+    /// assert_eq!(a.col_sums(), vec![3., 3.]);
+    /// ```
+    pub fn row_sums(&self) -> Vec<N> where N: Num {
+        self.fold_rows(N::zero(), |acc, val| acc + val)
+    }
+
+    /// Sum of the elements of each column, for any storage
+    pub fn col_sums(&self) -> Vec<N> where N: Num {
+        self.fold_cols(N::zero(), |acc, val| acc + val)
+    }
+
+    /// Largest absolute value of each row, for any storage. Empty rows
+    /// give zero.
+    pub fn max_abs_per_row(&self) -> Vec<N> where N: Signed + PartialOrd {
+        self.fold_rows(N::zero(), max_abs)
+    }
+
+    /// The 1-norm of the matrix, ie its largest absolute column sum
+    pub fn norm_one(&self) -> N where N: Signed + PartialOrd {
+        let sums = self.fold_cols(N::zero(), |acc, val| acc + val.abs());
+        sums.into_iter().fold(N::zero(), max_abs)
+    }
+
+    /// The infinity-norm of the matrix, ie its largest absolute row sum
+    pub fn norm_inf(&self) -> N where N: Signed + PartialOrd {
+        let sums = self.fold_rows(N::zero(), |acc, val| acc + val.abs());
+        sums.into_iter().fold(N::zero(), max_abs)
+    }
+
+    /// The Frobenius norm of the matrix, ie the square root of the sum
+    /// of its squared elements
+    pub fn norm_frobenius(&self) -> N where N: Float {
+        let sums = self.fold_outer(N::zero(), |acc, val| acc + val * val);
+        sums.into_iter().fold(N::zero(), |acc, val| acc + val).sqrt()
+    }
+
+    /// The largest absolute value of the elements of the matrix
+    pub fn norm_max_abs(&self) -> N where N: Signed + PartialOrd {
+        let maxs = self.fold_outer(N::zero(), max_abs);
+        maxs.into_iter().fold(N::zero(), max_abs)
+    }
+}
+
+fn max_abs<N: Signed + PartialOrd>(acc: N, val: N) -> N {
+    let val = val.abs();
+    if val > acc { val } else { acc }
 }
 
 impl<N, IptrStorage, IndStorage, DataStorage>
@@ -787,6 +1132,13 @@ DataStorage: DerefMut<Target=[N]> {
         &mut self.data[..]
     }
 
+    /// Mutable access to the non zero values, along with read access
+    /// to indptr and indices. This enables refilling the values of a matrix
+    /// while iterating over its structure.
+    pub fn structure_data_mut(&mut self) -> (&[usize], &[usize], &mut [N]) {
+        (&self.indptr[..], &self.indices[..], &mut self.data[..])
+    }
+
     /// Sparse matrix self-multiplication by a scalar
     pub fn scale(&mut self, val: N) where N: Num + Copy {
         for data in self.data_mut() {
@@ -794,6 +1146,96 @@ DataStorage: DerefMut<Target=[N]> {
         }
     }
 
+    /// Replace in place each value of this matrix by the result of f,
+    /// which receives the row, the column and the current value of each
+    /// non zero element.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use sprs::{CsMat, CSR};
+    /// let mut a = CsMat::new_owned(CSR, 2, 2, vec![0, 2, 3], vec![0, 1, 1],
+    ///                              vec![1., 5., -3f64]).unwrap();
+    /// // keep the diagonal, clamp the rest
+    /// a.map_inplace(|row, col, val| {
+    ///     if row == col { val } else { val.max(-2.).min(2.) }
+    /// });
+    /// assert_eq!(a.data(), &[1., 2., -3.]);
+    /// ```
+    pub fn map_inplace<F>(&mut self, mut f: F)
+    where F: FnMut(usize, usize, N) -> N {
+        let storage = self.storage;
+        let (indptr, indices, data) = self.structure_data_mut();
+        for (outer, range) in indptr.windows(2).enumerate() {
+            for pos in range[0]..range[1] {
+                let (row, col) = match storage {
+                    CSR => (outer, indices[pos]),
+                    CSC => (indices[pos], outer),
+                };
+                data[pos] = f(row, col, data[pos]);
+            }
+        }
+    }
+
+}
+
+/// Check the structure of compressed storage arrays before building a
+/// matrix from them, reporting the location of the first problem found.
+///
+/// The checked properties are listed in
+/// `CsMat::check_compressed_structure`.
+pub fn check_compressed_structure_raw<N>(storage: CompressedStorage,
+                                         nrows: usize,
+                                         ncols: usize,
+                                         indptr: &[usize],
+                                         indices: &[usize],
+                                         data: &[N]
+                                        ) -> Result<(), StructureError> {
+    let (outer, inner) = match storage {
+        CSR => (nrows, ncols),
+        CSC => (ncols, nrows),
+    };
+    if indptr.len() != outer + 1 {
+        return Err(StructureError::BadIndptrLength {
+            expected: outer + 1,
+            found: indptr.len(),
+        });
+    }
+    if indices.len() != data.len() {
+        return Err(StructureError::DataIndicesMismatch {
+            indices_len: indices.len(),
+            data_len: data.len(),
+        });
+    }
+    let nnz = indices.len();
+    // indptr values cannot exceed usize::MAX / 2 since nnz cannot
+    for (position, &value) in indptr.iter().enumerate() {
+        if value > nnz {
+            return Err(StructureError::OutOfBoundsIndptr {
+                position,
+                value,
+                nnz,
+            });
+        }
+    }
+
+    for (position, window) in indptr.windows(2).enumerate() {
+        if window[0] > window[1] {
+            return Err(StructureError::UnsortedIndptr {
+                position: position + 1,
+                value: window[1],
+                previous: window[0],
+            });
+        }
+    }
+
+    // check that the indices are sorted for each row
+    for (outer_ind, window) in indptr.windows(2).enumerate() {
+        utils::check_sparse_indices(&indices[window[0]..window[1]], inner,
+                                    Some(outer_ind), window[0])?;
+    }
+
+    Ok(())
 }
 
 mod raw {
@@ -903,6 +1345,34 @@ where N: 'a + Copy + Num,
     }
 }
 
+impl<'a, 'b, N, IpStorage, IStorage, DStorage> Mul<&'b [N]>
+for &'a CsMat<N, IpStorage, IStorage, DStorage>
+where N: 'a + Copy + Num,
+      IpStorage: 'a + Deref<Target=[usize]>,
+      IStorage: 'a + Deref<Target=[usize]>,
+      DStorage: 'a + Deref<Target=[N]> {
+    type Output = Result<Vec<N>, SprsError>;
+
+    fn mul(self, rhs: &'b [N]) -> Result<Vec<N>, SprsError> {
+        let mut res = vec![N::zero(); self.rows()];
+        self.mul_dense_vec(rhs, &mut res)?;
+        Ok(res)
+    }
+}
+
+impl<'a, 'b, N, IpStorage, IStorage, DStorage> Mul<&'b Vec<N>>
+for &'a CsMat<N, IpStorage, IStorage, DStorage>
+where N: 'a + Copy + Num,
+      IpStorage: 'a + Deref<Target=[usize]>,
+      IStorage: 'a + Deref<Target=[usize]>,
+      DStorage: 'a + Deref<Target=[N]> {
+    type Output = Result<Vec<N>, SprsError>;
+
+    fn mul(self, rhs: &'b Vec<N>) -> Result<Vec<N>, SprsError> {
+        self * &rhs[..]
+    }
+}
+
 impl<'a, 'b, N, IpS1, IS1, DS1, IpS2, IS2, DS2>
 Mul<&'b CsMat<N, IpS2, IS2, DS2>>
 for &'a CsMat<N, IpS1, IS1, DS1>
@@ -918,23 +1388,23 @@ where N: 'a + Copy + Num + Default,
     fn mul(self, rhs: &'b CsMat<N, IpS2, IS2, DS2>) -> CsMatOwned<N> {
         match (self.storage(), rhs.storage()) {
             (CSR, CSR) => {
-                let mut workspace = prod::workspace_csr(self, rhs);
-                prod::csr_mul_csr(self, rhs, &mut workspace).unwrap()
+                let mut spa = SparseAccumulator::new(rhs.cols());
+                prod::csr_mul_csr_spa(self, rhs, &mut spa).unwrap()
             }
             (CSR, CSC) => {
-                let mut workspace = prod::workspace_csr(self, rhs);
-                prod::csr_mul_csr(self,
-                                  &rhs.to_other_storage(),
-                                  &mut workspace).unwrap()
+                let mut spa = SparseAccumulator::new(rhs.cols());
+                prod::csr_mul_csr_spa(self,
+                                      &rhs.to_other_storage(),
+                                      &mut spa).unwrap()
             }
             (CSC, CSR) => {
-                let mut workspace = prod::workspace_csc(self, rhs);
-                prod::csc_mul_csc(self, &rhs.to_other_storage(),
-                                  &mut workspace).unwrap()
+                let mut spa = SparseAccumulator::new(self.rows());
+                prod::csc_mul_csc_spa(self, &rhs.to_other_storage(),
+                                      &mut spa).unwrap()
             }
             (CSC, CSC) => {
-                let mut workspace = prod::workspace_csc(self, rhs);
-                prod::csc_mul_csc(self, rhs, &mut workspace).unwrap()
+                let mut spa = SparseAccumulator::new(self.rows());
+                prod::csc_mul_csc_spa(self, rhs, &mut spa).unwrap()
             }
         }
     }
@@ -983,6 +1453,49 @@ where N: 'a + Copy + Num + Default,
     }
 }
 
+impl<'a, 'b, N, IpS, IS, DS, DS2>
+Mul<&'b CsMat<N, IpS, IS, DS>>
+for &'a Tensor<N, [usize; 2], DS2>
+where N: 'a + Copy + Num + Default,
+      IpS: 'b + Deref<Target=[usize]>,
+      IS: 'b + Deref<Target=[usize]>,
+      DS: 'b + Deref<Target=[N]>,
+      DS2: 'a + Deref<Target=[N]> {
+    type Output = MatOwned<N>;
+
+    fn mul(self, rhs: &'b CsMat<N, IpS, IS, DS>) -> MatOwned<N> {
+        let rows = self.rows();
+        let cols = rhs.cols();
+        match (self.ordering(), rhs.storage()) {
+            (StorageOrder::C, CSR) => {
+                let mut res = Tensor::zeros([rows, cols]);
+                prod::dense_rowmaj_mulacc_csr(self.borrowed(), rhs.borrowed(),
+                                              res.borrowed_mut()).unwrap();
+                res
+            }
+            (StorageOrder::C, CSC) => {
+                let mut res = Tensor::zeros([rows, cols]);
+                prod::dense_rowmaj_mulacc_csc(self.borrowed(), rhs.borrowed(),
+                                              res.borrowed_mut()).unwrap();
+                res
+            }
+            (StorageOrder::F, CSR) => {
+                let mut res = Tensor::zeros_f([rows, cols]);
+                prod::dense_colmaj_mulacc_csr(self.borrowed(), rhs.borrowed(),
+                                              res.borrowed_mut()).unwrap();
+                res
+            }
+            (StorageOrder::F, CSC) => {
+                let mut res = Tensor::zeros_f([rows, cols]);
+                prod::dense_colmaj_mulacc_csc(self.borrowed(), rhs.borrowed(),
+                                              res.borrowed_mut()).unwrap();
+                res
+            }
+            (StorageOrder::Unordered, _) => unreachable!("mats are ordered")
+        }
+    }
+}
+
 impl<'a, 'b, N, IpS, IS, DS, DS2>
 Add<&'b Tensor<N, [usize; 2], DS2>>
 for &'a CsMat<N, IpS, IS, DS>
@@ -1169,6 +1682,120 @@ mod test {
                                  indptr_ok, indices_ok, data_ok).is_ok());
     }
 
+    #[test]
+    fn check_structure_detailed() {
+        use errors::StructureError;
+        use super::check_compressed_structure_raw;
+        let indptr: &[usize] = &[0, 2, 4, 5, 6, 7];
+        let indices: &[usize] = &[2, 3, 3, 4, 2, 1, 3];
+        let data: &[f64] = &[1.; 7];
+        assert_eq!(check_compressed_structure_raw(CSR, 5, 5, indptr,
+                                                  indices, data),
+                   Ok(()));
+        assert_eq!(check_compressed_structure_raw(CSR, 5, 5, &indptr[1..],
+                                                  indices, data),
+                   Err(StructureError::BadIndptrLength {
+                       expected: 6, found: 5 }));
+        assert_eq!(check_compressed_structure_raw(CSR, 5, 5, indptr,
+                                                  indices, &data[1..]),
+                   Err(StructureError::DataIndicesMismatch {
+                       indices_len: 7, data_len: 6 }));
+        assert_eq!(check_compressed_structure_raw(CSR, 5, 5,
+                                                  &[0, 2, 8, 5, 6, 7],
+                                                  indices, data),
+                   Err(StructureError::OutOfBoundsIndptr {
+                       position: 2, value: 8, nnz: 7 }));
+        assert_eq!(check_compressed_structure_raw(CSR, 5, 5,
+                                                  &[0, 2, 4, 3, 6, 7],
+                                                  indices, data),
+                   Err(StructureError::UnsortedIndptr {
+                       position: 3, value: 3, previous: 4 }));
+        assert_eq!(check_compressed_structure_raw(CSR, 5, 5, indptr,
+                                                  &[2, 3, 4, 3, 2, 1, 3],
+                                                  data),
+                   Err(StructureError::NonSortedIndices {
+                       outer: Some(1), position: 3, index: 3, previous: 4 }));
+        assert_eq!(check_compressed_structure_raw(CSR, 5, 4, indptr,
+                                                  indices, data),
+                   Err(StructureError::OutOfBoundsIndex {
+                       outer: Some(1), position: 3, index: 4, bound: 4 }));
+
+        // the detailed error converts to the plain variant
+        let err = check_compressed_structure_raw(CSR, 5, 4, indptr,
+                                                 indices, data).unwrap_err();
+        assert_eq!(err.kind(), SprsError::OutOfBoundsIndex);
+        assert_eq!(CsMat::new_borrowed(CSR, 5, 4, indptr, indices, data),
+                   Err(SprsError::OutOfBoundsIndex));
+
+
+        // the detailed constructors forward the location to the caller
+        let expected = StructureError::OutOfBoundsIndex {
+            outer: Some(1), position: 3, index: 4, bound: 4 };
+        assert_eq!(CsMat::new_borrowed_detailed(CSR, 5, 4, indptr,
+                                                indices, data),
+                   Err(SprsError::Structure(expected.clone())));
+        let err = CsMat::new_owned_detailed(CSR, 5, 4, indptr.to_vec(),
+                                            indices.to_vec(), data.to_vec())
+            .unwrap_err();
+        assert_eq!(err, SprsError::Structure(expected));
+        assert_eq!(format!("{}", err),
+                   "index out of bounds: indices[3] = 4 is not lower than 4 \
+                    in outer dimension 1");
+        assert!(CsMat::new_owned_detailed(CSR, 5, 5, indptr.to_vec(),
+                                          indices.to_vec(), data.to_vec())
+                    .is_ok());
+    }
+
+    #[test]
+    fn new_owned_unsorted() {
+        let indptr = vec![0, 3, 3, 5, 6, 7];
+        let indices = vec![3, 2, 3, 4, 3, 1, 3];
+        let data = vec![1., 3., 3., 5., 2., 8., 7.];
+        let a = CsMat::new_owned_unsorted(CSR, 5, 5, indptr, indices, data,
+                                          |x, y| x + y).unwrap();
+        let expected = CsMat::new_owned(CSR, 5, 5,
+                                        vec![0, 2, 2, 4, 5, 6],
+                                        vec![2, 3, 3, 4, 1, 3],
+                                        vec![3., 4., 2., 5., 8., 7.]).unwrap();
+        assert_eq!(a, expected);
+
+        let a = CsMat::new_owned_unsorted(CSC, 3, 2,
+                                          vec![0, 2, 3],
+                                          vec![2, 0, 1],
+                                          vec![1, 2, 3],
+                                          |x, y| x + y).unwrap();
+        assert_eq!(a.indices(), &[0, 2, 1]);
+        assert_eq!(a.data(), &[2, 1, 3]);
+    }
+
+    #[test]
+    fn new_owned_unsorted_fails() {
+        let combine = |x: f64, y: f64| x + y;
+        assert_eq!(CsMat::new_owned_unsorted(CSR, 2, 2, vec![0, 1],
+                                             vec![0], vec![1.], combine),
+                   Err(SprsError::BadIndptrLength));
+        assert_eq!(CsMat::new_owned_unsorted(CSR, 2, 2, vec![0, 1, 2],
+                                             vec![0], vec![1., 1.], combine),
+                   Err(SprsError::DataIndicesMismatch));
+        assert_eq!(CsMat::new_owned_unsorted(CSR, 2, 2, vec![0, 2, 1],
+                                             vec![0, 1], vec![1., 1.],
+                                             combine),
+                   Err(SprsError::UnsortedIndptr));
+        // indptr has to start at 0, otherwise data[0] would be dropped
+        assert_eq!(CsMat::new_owned_unsorted(CSR, 3, 3, vec![1, 2, 3, 3],
+                                             vec![0, 1, 2], vec![1., 2., 3.],
+                                             combine),
+                   Err(SprsError::UnsortedIndptr));
+        assert_eq!(CsMat::new_owned_unsorted(CSR, 2, 2, vec![0, 1, 3],
+                                             vec![0, 1], vec![1., 1.],
+                                             combine),
+                   Err(SprsError::OutOfBoundsIndptr));
+        assert_eq!(CsMat::new_owned_unsorted(CSR, 2, 2, vec![0, 1, 2],
+                                             vec![0, 2], vec![1., 1.],
+                                             combine),
+                   Err(SprsError::OutOfBoundsIndex));
+    }
+
     #[test]
     fn new_csr_with_empty_row() {
         let indptr: &[usize] = &[0, 3, 3, 5, 6, 7];
@@ -1197,6 +1824,80 @@ mod test {
         assert_eq!(a.data(), c_true.data());
     }
 
+    #[test]
+    fn mul_dense_vec() {
+        let a = mat1();
+        let x = vec![1., 2., 3., 4., 5.];
+        let mut expected_output = vec![0.; 5];
+        ::sparse::prod::mul_acc_mat_vec_csr(a.borrowed(), &x,
+                                            &mut expected_output).unwrap();
+        assert_eq!((&a * &x).unwrap(), expected_output);
+        assert_eq!((&mat1_csc() * &x[..]).unwrap(), expected_output);
+
+        let mut res = vec![1.; 5];
+        mat1_csc().mul_dense_vec(&x, &mut res).unwrap();
+        assert_eq!(res, expected_output);
+
+        assert_eq!(&a * &x[..4], Err(SprsError::IncompatibleDimensions));
+        let res = a.mul_dense_vec(&x, &mut [0.; 3]);
+        assert_eq!(res, Err(SprsError::IncompatibleDimensions));
+    }
+
+    #[test]
+    fn map_and_zip() {
+        let a = mat1();
+        let a_f32 = a.map(|val| val as f32);
+        assert_eq!(a_f32.indptr(), a.indptr());
+        assert_eq!(a_f32.indices(), a.indices());
+        assert_eq!(a_f32.map(|val| 2. * val as f64), mat1_times_2());
+
+        let doubled = a.zip_map(&a, |x, y| x + y).unwrap();
+        assert_eq!(doubled, mat1_times_2());
+        let err = a.zip_map(&mat1_csc(), |x, y| x + y);
+        assert_eq!(err, Err(SprsError::IncompatibleStorages));
+        let eye: CsMatOwned<f64> = CsMat::eye(CSR, 5);
+        let err = a.zip_map(&eye, |x, y| x + y);
+        assert_eq!(err, Err(SprsError::IncompatiblePatterns));
+
+        // zero the upper triangle, in both storages
+        for mut mat in [mat1(), mat1_csc()] {
+            mat.map_inplace(|row, col, val| if col > row { 0. } else { val });
+            for (row, vec) in mat.to_csr().outer_iterator() {
+                for (col, val) in vec.iter() {
+                    let expected = if col > row { 0. }
+                                   else { a.at(&(row, col)).unwrap() };
+                    assert_eq!(val, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reductions_and_norms() {
+        let a = mat1();
+        let a_csc = mat1_csc();
+        let row_sums = vec![7., 7., 5., 8., 7.];
+        let col_sums = vec![0., 8., 8., 13., 5.];
+        assert_eq!(a.row_sums(), row_sums);
+        assert_eq!(a.sum_outer(), row_sums);
+        assert_eq!(a.col_sums(), col_sums);
+        assert_eq!(a.sum_inner(), col_sums);
+        assert_eq!(a_csc.row_sums(), row_sums);
+        assert_eq!(a_csc.sum_inner(), row_sums);
+        assert_eq!(a_csc.col_sums(), col_sums);
+
+        let b = &a * -1.;
+        assert_eq!(b.max_abs_per_row(), vec![4., 5., 5., 8., 7.]);
+        assert_eq!(b.to_csc().max_abs_per_row(), vec![4., 5., 5., 8., 7.]);
+        for mat in &[b.to_owned(), b.to_csc()] {
+            assert_eq!(mat.norm_one(), 13.);
+            assert_eq!(mat.norm_inf(), 8.);
+            assert_eq!(mat.norm_max_abs(), 8.);
+            let frob: f64 = 9. + 16. + 4. + 25. + 25. + 64. + 49.;
+            assert!((mat.norm_frobenius() - frob.sqrt()).abs() < 1e-12);
+        }
+    }
+
     #[test]
     fn outer_block_iter() {
         let mat : CsMatOwned<f64> = CsMat::eye(CSR, 11);
@@ -1213,4 +1914,25 @@ mod test {
         block_iter.next().unwrap();
         assert_eq!(block_iter.next(), None);
     }
+
+    #[test]
+    fn outer_iterator_perm() {
+        use sparse::permutation::Permutation;
+        // not an involution: its inverse is [2, 0, 1]
+        let perm = Permutation::new(vec![1, 2, 0]);
+        let csr = CsMat::new_owned(CSR, 3, 3, vec![0, 1, 3, 4],
+                                   vec![0, 0, 2, 1],
+                                   vec![1., 2., 3., 4.]).unwrap();
+        let csc = csr.to_csc();
+        for mat in &[csr.borrowed(), csc.borrowed()] {
+            let outer: Vec<_> = mat.outer_iterator_perm(perm.borrowed())
+                                   .map(|(outer_ind, _)| outer_ind)
+                                   .collect();
+            assert_eq!(outer, vec![1, 2, 0]);
+            let (_, vec) = mat.outer_iterator_perm(perm.borrowed())
+                              .next()
+                              .unwrap();
+            assert_eq!(vec, mat.outer_view(1).unwrap());
+        }
+    }
 }
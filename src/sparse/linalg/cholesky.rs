@@ -2,8 +2,9 @@
 
 use std::ops::Deref;
 use std::ops::IndexMut;
+use std::cmp;
 
-use num::traits::Num;
+use num::traits::{Num, Float, Signed};
 use std::ops::{Add, Sub, Mul, Div};
 
 use sparse::csmat::{self, CsMat, CsMatView};
@@ -12,6 +13,7 @@ use sparse::permutation::{Permutation, PermOwned};
 use utils::csmat_borrowed_uchk;
 use sparse::linalg::{self, etree};
 use stack::DStack;
+use errors::SprsError;
 
 pub enum SymmetryCheck {
     CheckSymmetry,
@@ -36,10 +38,18 @@ pub struct LdlNumeric<N> {
     diag: Vec<N>,
     y_workspace: Vec<N>,
     pattern_workspace: DStack<usize>,
+    singular_pivot: Option<usize>,
 }
 
 impl LdlSymbolic {
-    pub fn new<N, IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>) -> LdlSymbolic
+    /// Compute the symbolic decomposition of a symmetric matrix
+    ///
+    /// # Errors
+    ///
+    /// * `NonSquareMatrix` if the matrix is not square
+    /// * `NonSymmetricMatrix` if the matrix is not symmetric
+    pub fn new<N, IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>)
+                               -> Result<LdlSymbolic, SprsError>
     where N: Copy + PartialEq,
           IpS: Deref<Target = [usize]>,
           IS: Deref<Target = [usize]>,
@@ -49,16 +59,25 @@ impl LdlSymbolic {
         LdlSymbolic::new_perm(mat, perm)
     }
 
+    /// Compute the symbolic decomposition of a symmetric matrix, after
+    /// applying the symmetric permutation `perm`
+    ///
+    /// # Errors
+    ///
+    /// * `NonSquareMatrix` if the matrix is not square
+    /// * `NonSymmetricMatrix` if the matrix is not symmetric
     pub fn new_perm<N, IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>,
                                     perm: PermOwned)
-                                    -> LdlSymbolic
+                                    -> Result<LdlSymbolic, SprsError>
     where N: Copy + PartialEq,
           IpS: Deref<Target = [usize]>,
           IS: Deref<Target = [usize]>,
           DS: Deref<Target = [N]>
     {
         let n = mat.cols();
-        assert!(mat.rows() == n, "matrix should be square");
+        if mat.rows() != n {
+            return Err(SprsError::NonSquareMatrix);
+        }
         let mut l_colptr = vec![0; n+1];
         let mut parents = linalg::etree::ParentsOwned::new(n);
         let mut l_nz = vec![0; n];
@@ -69,15 +88,15 @@ impl LdlSymbolic {
                      parents.borrowed_mut(),
                      &mut l_nz,
                      &mut flag_workspace,
-                     SymmetryCheck::CheckSymmetry);
+                     SymmetryCheck::CheckSymmetry)?;
 
-        LdlSymbolic {
+        Ok(LdlSymbolic {
             colptr: l_colptr,
             parents: parents,
             nz: l_nz,
             flag_workspace: flag_workspace,
             perm: perm,
-        }
+        })
     }
 
     #[inline]
@@ -91,9 +110,17 @@ impl LdlSymbolic {
         self.colptr[n]
     }
 
+    /// Compute the numeric decomposition of a matrix sharing the
+    /// nonzero pattern used to compute this symbolic decomposition
+    ///
+    /// # Errors
+    ///
+    /// * `IncompatibleDimensions` if the matrix dimension differs from
+    ///   the symbolic decomposition's
+    /// * `SingularMatrix` if a zero pivot is met
     pub fn factor<N, IpS, IS, DS>(self,
                                   mat: &CsMat<N, IpS, IS, DS>)
-                                  -> LdlNumeric<N>
+                                  -> Result<LdlNumeric<N>, SprsError>
     where N: Copy + Num + PartialOrd,
           IpS: Deref<Target = [usize]>,
           IS: Deref<Target = [usize]>,
@@ -113,43 +140,75 @@ impl LdlSymbolic {
             diag: diag,
             y_workspace: y_workspace,
             pattern_workspace: pattern_workspace,
+            singular_pivot: None,
         };
-        ldl_numeric.update(mat);
-        ldl_numeric
+        ldl_numeric.update(mat)?;
+        Ok(ldl_numeric)
     }
 }
 
 impl<N> LdlNumeric<N> {
 
-    pub fn new<IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>) -> Self
+    /// Compute the LDLT decomposition of a symmetric matrix
+    ///
+    /// # Errors
+    ///
+    /// See `LdlSymbolic::new` and `LdlSymbolic::factor`
+    pub fn new<IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>)
+                            -> Result<Self, SprsError>
     where N: Copy + Num + PartialOrd,
           IpS: Deref<Target = [usize]>,
           IS: Deref<Target = [usize]>,
           DS: Deref<Target = [N]>
     {
-        let symbolic = LdlSymbolic::new(mat);
+        let symbolic = LdlSymbolic::new(mat)?;
         symbolic.factor(mat)
     }
 
+    /// Compute the LDLT decomposition of a symmetric matrix, after
+    /// applying the symmetric permutation `perm`
+    ///
+    /// # Errors
+    ///
+    /// See `LdlSymbolic::new_perm` and `LdlSymbolic::factor`
     pub fn new_perm<IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>,
                                  perm: PermOwned)
-                                 -> Self
+                                 -> Result<Self, SprsError>
     where N: Copy + Num + PartialOrd,
           IpS: Deref<Target = [usize]>,
           IS: Deref<Target = [usize]>,
           DS: Deref<Target = [N]>
     {
-        let symbolic = LdlSymbolic::new_perm(mat, perm);
+        let symbolic = LdlSymbolic::new_perm(mat, perm)?;
         symbolic.factor(mat)
     }
 
+    /// Recompute the numeric decomposition for a matrix sharing the
+    /// nonzero pattern of the factored matrix
+    ///
+    /// If an error is returned, the decomposition is left in an
+    /// unspecified state and should not be used for solving until a
+    /// successful update.
+    ///
+    /// # Errors
+    ///
+    /// * `IncompatibleDimensions` if the matrix dimension differs from
+    ///   the decomposition's
+    /// * `SingularMatrix` if a zero pivot is met, its index is then
+    ///   given by `singular_pivot`
     pub fn update<IpS, IS, DS>(&mut self, mat: &CsMat<N, IpS, IS, DS>)
+                               -> Result<(), SprsError>
     where N: Copy + Num + PartialOrd,
           IpS: Deref<Target = [usize]>,
           IS: Deref<Target = [usize]>,
           DS: Deref<Target = [N]>
     {
-        ldl_numeric(mat.borrowed(),
+        self.singular_pivot = None;
+        let n = self.symbolic.dim();
+        if mat.rows() != n || mat.cols() != n {
+            return Err(SprsError::IncompatibleDimensions);
+        }
+        let res = ldl_numeric(mat.borrowed(),
                     &self.symbolic.colptr,
                     self.symbolic.parents.borrowed(),
                     &self.symbolic.perm,
@@ -160,6 +219,18 @@ impl<N> LdlNumeric<N> {
                     &mut self.y_workspace,
                     &mut self.pattern_workspace,
                     &mut self.symbolic.flag_workspace);
+        if res.is_err() {
+            // the pivots preceding the failing one are nonzero
+            self.singular_pivot = self.diag.iter()
+                                      .position(|&d| d == N::zero());
+        }
+        res
+    }
+
+    /// The index of the zero pivot met by the last update, in the
+    /// permuted ordering, if it failed with `SingularMatrix`
+    pub fn singular_pivot(&self) -> Option<usize> {
+        self.singular_pivot
     }
 
     pub fn solve<'a, V, M>(&self, rhs: &V) -> Vec<M>
@@ -181,9 +252,231 @@ impl<N> LdlNumeric<N> {
         let pinv = self.symbolic.perm.inv();
         &pinv * &x
     }
+
+    /// Estimate the 1-norm of the inverse of the factored matrix
+    ///
+    /// This is the block estimator of Higham and Tisseur with two columns,
+    /// see `inverse_norm1_est_block`.
+    pub fn inverse_norm1_est(&self) -> N
+    where N: Float {
+        self.inverse_norm1_est_block(2)
+    }
+
+    /// Estimate the 1-norm of the inverse of the factored matrix, using
+    /// the block method of Higham and Tisseur with t columns.
+    ///
+    /// Each iteration performs 2t solves with the factorization, and at
+    /// most 5 iterations are performed. Larger values of t give more
+    /// reliable estimates, t = 1 being Hager's method. The estimate is a
+    /// lower bound of the exact norm, which is seldom off by more than a
+    /// factor 3 in practice. The norm is computed exactly when t is not
+    /// lower than the dimension.
+    ///
+    /// # Panics
+    ///
+    /// If t is zero
+    pub fn inverse_norm1_est_block(&self, t: usize) -> N
+    where N: Float {
+        assert!(t > 0, "the block should have at least one column");
+        let n = self.symbolic.dim();
+        let norm1 = |v: &[N]| v.iter().fold(N::zero(), |acc, &x| acc + x.abs());
+        let signs = |v: &[N]| -> Vec<N> {
+            v.iter().map(|&x| if x >= N::zero() { N::one() }
+                              else { -N::one() }).collect()
+        };
+        let unit = |j: usize| {
+            let mut e_j = vec![N::zero(); n];
+            e_j[j] = N::one();
+            e_j
+        };
+        if t >= n {
+            return (0..n).map(|j| norm1(&self.solve(&unit(j))))
+                         .fold(N::zero(), N::max);
+        }
+
+        // the first column is uniform, the others have random signs
+        let mut seed = 0x2545_f491_4f6c_dd1d;
+        let mut x = vec![vec![N::one(); n]];
+        for j in 1..t {
+            let mut col = random_signs(&mut seed, n);
+            while x[..j].iter().any(|other| parallel_signs(&col, other)) {
+                col = random_signs(&mut seed, n);
+            }
+            x.push(col);
+        }
+        let n_float = N::from(n).unwrap();
+        for col in &mut x {
+            for val in col.iter_mut() {
+                *val = *val / n_float;
+            }
+        }
+
+        let max_iter = 5;
+        let mut est = N::zero();
+        let mut est_old = N::zero();
+        // the unit vectors forming x, once past the first iteration
+        let mut ind = Vec::new();
+        let mut ind_best = 0;
+        let mut ind_hist = vec![false; n];
+        let mut s_old: Vec<Vec<N>> = Vec::new();
+        for k in 1..max_iter + 1 {
+            let y: Vec<Vec<N>> = x.iter().map(|col| self.solve(col)).collect();
+            let mut j_best = 0;
+            est = N::zero();
+            for (j, col) in y.iter().enumerate() {
+                let col_norm = norm1(col);
+                if col_norm > est {
+                    est = col_norm;
+                    j_best = j;
+                }
+            }
+            if k >= 2 && est <= est_old {
+                est = est_old;
+                break;
+            }
+            if k >= 2 {
+                ind_best = ind[j_best];
+            }
+            est_old = est;
+            if k == max_iter {
+                break;
+            }
+
+            let mut s: Vec<Vec<N>> = y.iter().map(|col| signs(col)).collect();
+            if k >= 2 && s.iter().all(|col| {
+                s_old.iter().any(|old| parallel_signs(col, old))
+            }) {
+                break;
+            }
+            // parallel columns would waste solves
+            if t > 1 {
+                for j in 0..t {
+                    while s[..j].iter().chain(&s_old).any(|other| {
+                        parallel_signs(&s[j], other)
+                    }) {
+                        s[j] = random_signs(&mut seed, n);
+                    }
+                }
+            }
+            // the factored matrix being symmetric, so is its inverse, which
+            // means solves can be used in place of transposed solves
+            let z: Vec<Vec<N>> = s.iter().map(|col| self.solve(col)).collect();
+            let h: Vec<N> = (0..n).map(|i| {
+                z.iter().fold(N::zero(), |acc, col| acc.max(col[i].abs()))
+            }).collect();
+            let h_max = h.iter().fold(N::zero(), |acc, &x| acc.max(x));
+            if k >= 2 && h_max == h[ind_best] {
+                break;
+            }
+            let mut order: Vec<usize> = (0..n).collect();
+            order.sort_by(|&i, &j| {
+                h[j].partial_cmp(&h[i]).unwrap_or(cmp::Ordering::Equal)
+            });
+            if t > 1 {
+                if order[..t].iter().all(|&i| ind_hist[i]) {
+                    break;
+                }
+                // prefer the unit vectors which have not been tried yet
+                let (fresh, seen): (Vec<usize>, Vec<usize>) =
+                    order.iter().partition(|&&i| !ind_hist[i]);
+                order = fresh;
+                order.extend(seen);
+            }
+            ind = order[..t].to_vec();
+            for &i in &ind {
+                ind_hist[i] = true;
+            }
+            x = ind.iter().map(|&i| unit(i)).collect();
+            s_old = s;
+        }
+        est
+    }
+
+    /// Estimate the condition number in the 1-norm of the factored matrix,
+    /// which must be given as mat since the factorization does not keep
+    /// track of it.
+    ///
+    /// See `inverse_norm1_est` for the estimation method.
+    ///
+    /// # Errors
+    ///
+    /// * `IncompatibleDimensions` if the matrix dimension differs from
+    ///   the decomposition's
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use sprs::{CsMat, CSC};
+    /// use sprs::sparse::linalg::cholesky::LdlNumeric;
+    /// let a = CsMat::new_owned(CSC, 2, 2, vec![0, 2, 4], vec![0, 1, 0, 1],
+    ///                          vec![1., 1., 1., 1.001]).unwrap();
+    /// let ldl = LdlNumeric::new(&a).unwrap();
+    /// let cond = ldl.condest(&a).unwrap();
+    /// assert!(cond > 1e3);
+    /// assert!(ldl.rcond(&a).unwrap() < 1e-3);
+    /// ```
+    pub fn condest<IpS, IS, DS>(&self, mat: &CsMat<N, IpS, IS, DS>)
+                                -> Result<N, SprsError>
+    where N: Float + Signed,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let n = self.symbolic.dim();
+        if mat.rows() != n || mat.cols() != n {
+            return Err(SprsError::IncompatibleDimensions);
+        }
+        Ok(mat.norm_one() * self.inverse_norm1_est())
+    }
+
+    /// Estimate the reciprocal of the condition number in the 1-norm of
+    /// the factored matrix, which must be given as mat. A value close to
+    /// the machine epsilon indicates a nearly singular matrix.
+    ///
+    /// # Errors
+    ///
+    /// * `IncompatibleDimensions` if the matrix dimension differs from
+    ///   the decomposition's
+    pub fn rcond<IpS, IS, DS>(&self, mat: &CsMat<N, IpS, IS, DS>)
+                              -> Result<N, SprsError>
+    where N: Float + Signed,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let cond = self.condest(mat)?;
+        if cond.is_finite() && cond > N::zero() {
+            Ok(cond.recip())
+        }
+        else {
+            Ok(N::zero())
+        }
+    }
+}
+
+/// Pseudo random vector of signs, using a xorshift generator so that
+/// the estimations of the norm of the inverse are reproducible
+fn random_signs<N: Float>(seed: &mut u64, n: usize) -> Vec<N> {
+    (0..n).map(|_| {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        if *seed >> 63 == 0 { N::one() } else { -N::one() }
+    }).collect()
+}
+
+/// Check whether two vectors of signs are parallel
+fn parallel_signs<N: Float>(a: &[N], b: &[N]) -> bool {
+    a == b || a.iter().zip(b).all(|(&x, &y)| x == -y)
 }
 
 /// Perform a symbolic LDLt decomposition of a symmetric sparse matrix
+///
+/// # Errors
+///
+/// * `NonSquareMatrix` if the matrix is not square
+/// * `NonSymmetricMatrix` if the symmetry is checked and the matrix
+///   is not symmetric
 pub fn ldl_symbolic<N, PStorage>(mat: CsMatView<N>,
                                  perm: &Permutation<PStorage>,
                                  l_colptr: &mut [usize],
@@ -191,14 +484,17 @@ pub fn ldl_symbolic<N, PStorage>(mat: CsMatView<N>,
                                  l_nz: &mut [usize],
                                  flag_workspace: &mut [usize],
                                  check_symmetry: SymmetryCheck)
+                                 -> Result<(), SprsError>
 where N: Clone + Copy + PartialEq,
       PStorage: Deref<Target = [usize]>
 {
-
+    if mat.rows() != mat.cols() {
+        return Err(SprsError::NonSquareMatrix);
+    }
     match check_symmetry {
         SymmetryCheck::DontCheckSymmetry => (),
         SymmetryCheck::CheckSymmetry => if !is_symmetric(&mat) {
-            panic!("Matrix is not symmetric")
+            return Err(SprsError::NonSymmetricMatrix);
         },
     }
 
@@ -233,11 +529,17 @@ where N: Clone + Copy + PartialEq,
     }
     l_colptr[n] = prev;
 
+    Ok(())
 }
 
 /// Perform numeric LDLT decomposition
 ///
 /// pattern_workspace is a DStack of capacity n
+///
+/// # Errors
+///
+/// * `SingularMatrix` if a zero pivot is met, the first zero value
+///   of diag then being the failing pivot
 pub fn ldl_numeric<N, PStorage>(mat: CsMatView<N>,
                                 l_colptr: &[usize],
                                 parents: etree::ParentsView,
@@ -249,6 +551,7 @@ pub fn ldl_numeric<N, PStorage>(mat: CsMatView<N>,
                                 y_workspace: &mut [N],
                                 pattern_workspace: &mut DStack<usize>,
                                 flag_workspace: &mut [usize])
+                                -> Result<(), SprsError>
 where N: Clone + Copy + PartialEq + Num + PartialOrd,
       PStorage: Deref<Target = [usize]>
 {
@@ -301,9 +604,10 @@ where N: Clone + Copy + PartialEq + Num + PartialOrd,
             l_nz[i] += 1;
         }
         if diag[k] == N::zero() {
-            panic!("Matrix is singular");
+            return Err(SprsError::SingularMatrix);
         }
     }
+    Ok(())
 }
 
 /// Triangular solve specialized on lower triangular matrices
@@ -346,6 +650,8 @@ mod test {
     use super::SymmetryCheck;
     use utils::csmat_borrowed_uchk;
     use stack::DStack;
+    use errors::SprsError;
+    use std::cmp;
 
     fn test_mat1() -> CsMatOwned<f64> {
         let indptr = vec![0, 2, 5, 6, 7, 13, 14, 17, 20, 24, 28];
@@ -443,7 +749,7 @@ mod test {
                             parents.borrowed_mut(),
                             &mut l_nz,
                             &mut flag_workspace,
-                            SymmetryCheck::CheckSymmetry);
+                            SymmetryCheck::CheckSymmetry).unwrap();
 
         let nnz = l_colptr[10];
         let mut l_indices = vec![0; nnz];
@@ -461,7 +767,7 @@ mod test {
                            &mut diag,
                            &mut y_workspace,
                            &mut pattern_workspace,
-                           &mut flag_workspace);
+                           &mut flag_workspace).unwrap();
 
         let (expected_lp, expected_li, expected_lx, expected_d) =
             expected_factors1();
@@ -499,7 +805,7 @@ mod test {
     fn test_factor_solve1() {
         let mat = test_mat1();
         let b = test_vec1();
-        let ldlt = super::LdlNumeric::new(&mat);
+        let ldlt = super::LdlNumeric::new(&mat).unwrap();
         let x = ldlt.solve(&b);
         let x0 = expected_res1();
         assert_eq!(x, x0);
@@ -528,10 +834,153 @@ mod test {
 
         let perm = Permutation::new(vec![0, 2, 1, 3]);
 
-        let ldlt = super::LdlNumeric::new_perm(&mat, perm);
+        let ldlt = super::LdlNumeric::new_perm(&mat, perm).unwrap();
         let b = vec![9, 60, 18, 34];
         let x0 = vec![1, 2, 3, 4];
         let x = ldlt.solve(&b);
         assert_eq!(x, x0);
     }
+
+    #[test]
+    fn permuted_ldl_solve_storages() {
+        // the permutation is not an involution, so it differs from its
+        // inverse
+        let b = test_vec1();
+        let perm = vec![3, 7, 0, 9, 1, 4, 8, 2, 5, 6];
+        let mat = test_mat1();
+        let csr = mat.to_csr();
+
+        let sym_csc = super::LdlSymbolic::new_perm(
+            &mat, Permutation::new(perm.clone())).unwrap();
+        let sym_csr = super::LdlSymbolic::new_perm(
+            &csr, Permutation::new(perm.clone())).unwrap();
+        assert_eq!(sym_csc.colptr, sym_csr.colptr);
+        assert_eq!(sym_csc.nz, sym_csr.nz);
+        let x = sym_csc.factor(&mat).unwrap().solve(&b);
+        assert_eq!(sym_csr.factor(&csr).unwrap().solve(&b), x);
+
+        let ldlt = super::LdlNumeric::new_perm(&mat,
+                                               Permutation::new(perm.clone()))
+                       .unwrap();
+        assert_eq!(ldlt.solve(&b), x);
+        let ldlt = super::LdlNumeric::new_perm(&csr, Permutation::new(perm))
+                       .unwrap();
+        assert_eq!(ldlt.solve(&b), x);
+        let res = (&mat * &x).unwrap();
+        for (r, b) in res.iter().zip(b.iter()) {
+            assert!((r - b).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn condition_number_estimate() {
+        // the exact 1-norm of the inverse, from its columns
+        let mat = test_mat1();
+        let ldlt = super::LdlNumeric::new(&mat).unwrap();
+        let n = mat.rows();
+        let inv_norm = (0..n).map(|j| {
+            let mut e_j = vec![0.; n];
+            e_j[j] = 1.;
+            ldlt.solve(&e_j).iter().map(|x: &f64| x.abs()).sum::<f64>()
+        }).fold(0., f64::max);
+        let est = ldlt.inverse_norm1_est();
+        assert!(est <= inv_norm * (1. + 1e-12));
+        assert!(est >= inv_norm / 3.);
+        let cond = ldlt.condest(&mat).unwrap();
+        assert!((cond - mat.norm_one() * est).abs() < 1e-12 * cond);
+        assert!((ldlt.rcond(&mat).unwrap() * cond - 1.).abs() < 1e-12);
+
+        // the estimation is exact for a diagonal matrix
+        let diag = CsMatOwned::new_owned(CSC, 3, 3, vec![0, 1, 2, 3],
+                                         vec![0, 1, 2],
+                                         vec![1., 0.5, 4.]).unwrap();
+        let ldlt = super::LdlNumeric::new(&diag).unwrap();
+        assert_eq!(ldlt.inverse_norm1_est(), 2.);
+        assert_eq!(ldlt.condest(&diag).unwrap(), 8.);
+        assert_eq!(ldlt.condest(&mat), Err(SprsError::IncompatibleDimensions));
+    }
+
+    #[test]
+    fn block_condition_number_estimate() {
+        // diagonally dominant band matrix with entries of both signs, so
+        // that the inverse has entries of both signs too
+        let n: usize = 40;
+        let mut indptr = vec![0];
+        let mut indices = Vec::new();
+        let mut data = Vec::new();
+        for col in 0..n {
+            let first = col.saturating_sub(2);
+            for row in first..cmp::min(col + 3, n) {
+                indices.push(row);
+                data.push(match col.max(row) - col.min(row) {
+                    0 => 4. + (col % 3) as f64,
+                    1 => -1.5,
+                    _ => 1.,
+                });
+            }
+            indptr.push(indices.len());
+        }
+        let mat = CsMatOwned::new_owned(CSC, n, n, indptr, indices,
+                                        data).unwrap();
+        let ldlt = super::LdlNumeric::new(&mat).unwrap();
+        let inv_norm = (0..n).map(|j| {
+            let mut e_j = vec![0.; n];
+            e_j[j] = 1.;
+            ldlt.solve(&e_j).iter().map(|x: &f64| x.abs()).sum::<f64>()
+        }).fold(0., f64::max);
+        for t in 1..5 {
+            let est = ldlt.inverse_norm1_est_block(t);
+            assert!(est <= inv_norm * (1. + 1e-12));
+            assert!(est >= inv_norm / 3.);
+        }
+        assert_eq!(ldlt.inverse_norm1_est(), ldlt.inverse_norm1_est_block(2));
+        // a block as large as the matrix gives the exact norm
+        let exact = ldlt.inverse_norm1_est_block(n);
+        assert!((exact - inv_norm).abs() < 1e-12 * inv_norm);
+    }
+
+    #[test]
+    fn ldl_errors() {
+        let non_square = CsMatOwned::new_owned(CSC, 3, 2,
+                                               vec![0, 1, 2],
+                                               vec![0, 1],
+                                               vec![1., 1.]).unwrap();
+        assert_eq!(super::LdlNumeric::new(&non_square).unwrap_err(),
+                   SprsError::NonSquareMatrix);
+
+        let non_symmetric = CsMatOwned::new_owned(CSC, 2, 2,
+                                                  vec![0, 2, 3],
+                                                  vec![0, 1, 1],
+                                                  vec![1., 2., 1.]).unwrap();
+        assert_eq!(super::LdlSymbolic::new(&non_symmetric).unwrap_err(),
+                   SprsError::NonSymmetricMatrix);
+
+        // |1 1 0|
+        // |1 1 0| has a zero pivot at index 1
+        // |0 0 1|
+        let singular = CsMatOwned::new_owned(CSC, 3, 3,
+                                             vec![0, 2, 4, 5],
+                                             vec![0, 1, 0, 1, 2],
+                                             vec![1., 1., 1., 1., 1.]).unwrap();
+        let symbolic = super::LdlSymbolic::new(&singular).unwrap();
+        assert_eq!(symbolic.factor(&singular).unwrap_err(),
+                   SprsError::SingularMatrix);
+
+        // a failed update can be recovered from
+        let regular = CsMatOwned::new_owned(CSC, 3, 3,
+                                            vec![0, 2, 4, 5],
+                                            vec![0, 1, 0, 1, 2],
+                                            vec![2., 1., 1., 2., 1.]).unwrap();
+        let mut ldlt = super::LdlNumeric::new(&regular).unwrap();
+        assert_eq!(ldlt.update(&singular),
+                   Err(SprsError::SingularMatrix));
+        assert_eq!(ldlt.singular_pivot(), Some(1));
+        ldlt.update(&regular).unwrap();
+        assert_eq!(ldlt.singular_pivot(), None);
+        let x = ldlt.solve(&vec![3., 3., 1.]);
+        assert_eq!(x, vec![1., 1., 1.]);
+
+        assert_eq!(ldlt.update(&CsMatOwned::<f64>::eye(CSC, 4)),
+                   Err(SprsError::IncompatibleDimensions));
+    }
 }
@@ -3,6 +3,10 @@
 
 use std::ops::{Deref, DerefMut};
 
+use sparse::csmat::CsMatView;
+use sparse::permutation::Permutation;
+use errors::SprsError;
+
 pub type Parent = Option<usize>;
 
 /// Store an etree as the parent information of each node.
@@ -46,6 +50,40 @@ impl<S: Deref<Target=[Parent]>> Parents<S> {
             parents: &self.parents[..]
         }
     }
+
+    /// Compute a postorder of the tree, ie an ordering where each node
+    /// comes after its descendants. The k-th element of the result is the
+    /// node at position k in the postorder.
+    pub fn postorder(&self) -> Vec<usize> {
+        let n = self.nb_nodes();
+        // linked lists of children, in increasing order
+        let mut first_child = vec![None; n];
+        let mut next_sibling = vec![None; n];
+        for node in (0..n).rev() {
+            if let Some(parent) = self.get_parent(node) {
+                next_sibling[node] = first_child[parent];
+                first_child[parent] = Some(node);
+            }
+        }
+        let mut post = Vec::with_capacity(n);
+        let mut stack = Vec::new();
+        for root in (0..n).filter(|&node| self.is_root(node)) {
+            stack.push(root);
+            while let Some(&node) = stack.last() {
+                match first_child[node] {
+                    Some(child) => {
+                        first_child[node] = next_sibling[child];
+                        stack.push(child);
+                    }
+                    None => {
+                        stack.pop();
+                        post.push(node);
+                    }
+                }
+            }
+        }
+        post
+    }
 }
 
 impl<S: DerefMut<Target=[Parent]>> Parents<S> {
@@ -98,3 +136,98 @@ impl Parents<Vec<Parent>> {
         }
     }
 }
+
+/// Compute the column elimination tree of a matrix A, ie the elimination
+/// tree of A^T A, without forming A^T A. This is the tree arising in the
+/// QR factorization of A.
+///
+/// The columns of A are considered in the order given by `col_perm`, ie
+/// the tree nodes are the columns of A P.
+///
+/// # Errors
+///
+/// * `BadStorageType` if the matrix is not CSC
+pub fn column_etree<N, PStorage>(mat: CsMatView<N>,
+                                 col_perm: &Permutation<PStorage>)
+                                 -> Result<ParentsOwned, SprsError>
+where N: Copy,
+      PStorage: Deref<Target = [usize]>
+{
+    if !mat.is_csc() {
+        return Err(SprsError::BadStorageType);
+    }
+    let n = mat.cols();
+    let mut parents = ParentsOwned::new(n);
+    // ancestor is a path compressed version of parents
+    let mut ancestor: Vec<Parent> = vec![None; n];
+    // prev holds for each row the last column where it was seen
+    let mut prev: Vec<Parent> = vec![None; mat.rows()];
+    for k in 0..n {
+        let col = mat.outer_view(col_perm.at(k)).expect("k is in bounds");
+        for &row in col.indices() {
+            // the columns sharing this row are all linked in A^T A
+            let mut node = prev[row];
+            while let Some(i) = node {
+                if i >= k {
+                    break;
+                }
+                node = ancestor[i];
+                ancestor[i] = Some(k);
+                if node.is_none() {
+                    parents.set_parent(i, k);
+                }
+            }
+            prev[row] = Some(k);
+        }
+    }
+    Ok(parents)
+}
+
+#[cfg(test)]
+mod test {
+    use sparse::csmat::{CsMat, CSR};
+    use sparse::permutation::Permutation;
+    use errors::SprsError;
+    use super::{column_etree, ParentsOwned};
+
+    #[test]
+    fn column_etree_of_mat() {
+        // |1 0 0 1|
+        // |0 1 0 0|
+        // |0 1 1 0|
+        // |0 0 0 1|
+        // |0 0 1 0|
+        let mat = CsMat::new_owned(CSR, 5, 4,
+                                   vec![0, 2, 3, 5, 6, 7],
+                                   vec![0, 3, 1, 1, 2, 3, 2],
+                                   vec![1.; 7]).unwrap();
+        let identity: Permutation<Vec<usize>> = Permutation::identity();
+        assert_eq!(column_etree(mat.borrowed(), &identity).unwrap_err(),
+                   SprsError::BadStorageType);
+
+        let mat = mat.to_csc();
+        let parents = column_etree(mat.borrowed(), &identity).unwrap();
+        let parents: Vec<_> = (0..4).map(|i| parents.get_parent(i)).collect();
+        assert_eq!(parents, vec![Some(3), Some(2), None, None]);
+
+        let perm = Permutation::new(vec![3, 2, 1, 0]);
+        let parents = column_etree(mat.borrowed(), &perm).unwrap();
+        let parents: Vec<_> = (0..4).map(|i| parents.get_parent(i)).collect();
+        assert_eq!(parents, vec![Some(3), Some(2), None, None]);
+    }
+
+    #[test]
+    fn postorder() {
+        //     4
+        //    / \
+        //   0   2    5
+        //      / \
+        //     1   3
+        let mut parents = ParentsOwned::new(6);
+        parents.set_parent(0, 4);
+        parents.set_parent(2, 4);
+        parents.set_parent(1, 2);
+        parents.set_parent(3, 2);
+        assert_eq!(parents.postorder(), vec![0, 1, 3, 2, 4, 5]);
+    }
+}
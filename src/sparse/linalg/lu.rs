@@ -0,0 +1,511 @@
+//! LU factorization of unsymmetric matrices
+//!
+//! This is a left looking Gilbert-Peierls factorization: each column of the
+//! factors is obtained by a sparse triangular solve with the already
+//! computed part of L, followed by the choice of a pivot using threshold
+//! partial pivoting.
+
+use std::cmp;
+use std::default::Default;
+use std::ops::Deref;
+
+use num::traits::Float;
+
+use sparse::csmat::{self, CsMat, CsMatOwned, CsMatView};
+use sparse::permutation::{Permutation, PermOwned};
+use sparse::linalg::trisolve;
+use utils::csmat_borrowed_uchk;
+use stack::{self, DStack, StackVal};
+use errors::SprsError;
+
+/// Structure holding the analysis of a matrix prior to its LU decomposition
+///
+/// As rows are chosen by partial pivoting during the numeric phase, the
+/// nonzero pattern of the factors is only known once the numeric
+/// decomposition has been performed. The symbolic phase fixes the
+/// column ordering and the pivoting parameters.
+#[derive(Debug)]
+pub struct LuSymbolic {
+    dim: usize,
+    col_perm: PermOwned,
+    pivot_threshold: f64,
+}
+
+/// Numeric LU decomposition of a square matrix A, such that
+/// P A Q = L U, where P is the row permutation chosen by pivoting, Q
+/// the column permutation of the symbolic decomposition, L is lower
+/// triangular with a unit diagonal, and U is upper triangular.
+#[derive(Debug)]
+pub struct LuNumeric<N> {
+    symbolic: LuSymbolic,
+    l: CsMatOwned<N>,
+    u: CsMatOwned<N>,
+    row_perm: PermOwned,
+    x_workspace: Vec<N>,
+    visited: Vec<bool>,
+    row_to_col: Vec<Option<usize>>,
+    pattern_workspace: DStack<StackVal<usize>>,
+    singular_pivot: Option<usize>,
+}
+
+impl LuSymbolic {
+    /// Prepare the LU decomposition of a square matrix
+    ///
+    /// # Errors
+    ///
+    /// * `NonSquareMatrix` if the matrix is not square
+    pub fn new<N, IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>)
+                               -> Result<LuSymbolic, SprsError>
+    where N: Copy,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let perm: Permutation<Vec<usize>> = Permutation::identity();
+        LuSymbolic::new_perm(mat, perm)
+    }
+
+    /// Prepare the LU decomposition of a square matrix, whose columns
+    /// will be eliminated in the order given by `col_perm`
+    ///
+    /// # Errors
+    ///
+    /// * `NonSquareMatrix` if the matrix is not square
+    pub fn new_perm<N, IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>,
+                                    col_perm: PermOwned)
+                                    -> Result<LuSymbolic, SprsError>
+    where N: Copy,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let n = mat.cols();
+        if mat.rows() != n {
+            return Err(SprsError::NonSquareMatrix);
+        }
+        Ok(LuSymbolic {
+            dim: n,
+            col_perm,
+            pivot_threshold: 1.,
+        })
+    }
+
+    #[inline]
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// The threshold used for partial pivoting
+    #[inline]
+    pub fn pivot_threshold(&self) -> f64 {
+        self.pivot_threshold
+    }
+
+    /// Set the threshold used for partial pivoting
+    ///
+    /// When eliminating the k-th column of the permuted matrix, the
+    /// diagonal row is kept as the pivot if its magnitude is at least the
+    /// threshold times the largest candidate magnitude. A threshold of 1
+    /// amounts to classical partial pivoting, while lower values favor
+    /// the diagonal, thus the column ordering and the sparsity of the
+    /// factors, at the expense of stability.
+    ///
+    /// # Panics
+    ///
+    /// If the threshold is not in the range ]0, 1]
+    pub fn set_pivot_threshold(&mut self, threshold: f64) {
+        assert!(threshold > 0. && threshold <= 1.,
+                "the pivot threshold should be in ]0, 1]");
+        self.pivot_threshold = threshold;
+    }
+
+    /// Compute the numeric decomposition of a matrix
+    ///
+    /// # Errors
+    ///
+    /// * `IncompatibleDimensions` if the matrix dimension differs from
+    ///   the symbolic decomposition's
+    /// * `SingularMatrix` if no nonzero pivot can be found
+    pub fn factor<N, IpS, IS, DS>(self,
+                                  mat: &CsMat<N, IpS, IS, DS>)
+                                  -> Result<LuNumeric<N>, SprsError>
+    where N: Float + Default,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let n = self.dim();
+        let empty = || {
+            CsMat::new_owned(csmat::CSC, n, n, vec![0; n + 1],
+                             Vec::new(), Vec::new())
+                .expect("empty matrix is valid")
+        };
+        let mut lu_numeric = LuNumeric {
+            symbolic: self,
+            l: empty(),
+            u: empty(),
+            row_perm: Permutation::identity(),
+            x_workspace: vec![N::zero(); n],
+            visited: vec![false; n],
+            row_to_col: vec![None; n],
+            pattern_workspace: DStack::with_capacity(cmp::max(2 * n, 2)),
+            singular_pivot: None,
+        };
+        lu_numeric.update(mat)?;
+        Ok(lu_numeric)
+    }
+}
+
+impl<N> LuNumeric<N> {
+
+    /// Compute the LU decomposition of a square matrix
+    ///
+    /// # Errors
+    ///
+    /// See `LuSymbolic::new` and `LuSymbolic::factor`
+    pub fn new<IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>)
+                            -> Result<Self, SprsError>
+    where N: Float + Default,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let symbolic = LuSymbolic::new(mat)?;
+        symbolic.factor(mat)
+    }
+
+    /// Compute the LU decomposition of a square matrix, whose columns
+    /// are eliminated in the order given by `col_perm`
+    ///
+    /// # Errors
+    ///
+    /// See `LuSymbolic::new_perm` and `LuSymbolic::factor`
+    pub fn new_perm<IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>,
+                                 col_perm: PermOwned)
+                                 -> Result<Self, SprsError>
+    where N: Float + Default,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let symbolic = LuSymbolic::new_perm(mat, col_perm)?;
+        symbolic.factor(mat)
+    }
+
+    /// Recompute the numeric decomposition for a matrix of the same
+    /// dimension as the factored matrix
+    ///
+    /// The pivots are chosen again, so the nonzero pattern of the
+    /// matrix may differ from the one of the factored matrix.
+    ///
+    /// If an error is returned, the decomposition is left in an
+    /// unspecified state and should not be used for solving until a
+    /// successful update.
+    ///
+    /// # Errors
+    ///
+    /// * `IncompatibleDimensions` if the matrix dimension differs from
+    ///   the decomposition's
+    /// * `SingularMatrix` if no nonzero pivot can be found, the index of
+    ///   the failing column in the column permuted ordering is then given
+    ///   by `singular_pivot`
+    pub fn update<IpS, IS, DS>(&mut self, mat: &CsMat<N, IpS, IS, DS>)
+                               -> Result<(), SprsError>
+    where N: Float + Default,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        self.singular_pivot = None;
+        let n = self.symbolic.dim();
+        if mat.rows() != n || mat.cols() != n {
+            return Err(SprsError::IncompatibleDimensions);
+        }
+        let mat_csc;
+        let mat = if mat.is_csc() {
+            mat.borrowed()
+        } else {
+            mat_csc = mat.to_csc();
+            mat_csc.borrowed()
+        };
+        let threshold = N::from(self.symbolic.pivot_threshold)
+                            .expect("pivot threshold should be representable");
+
+        let mut l_indptr = Vec::with_capacity(n + 1);
+        let mut l_indices = Vec::with_capacity(mat.nb_nonzero());
+        let mut l_data = Vec::with_capacity(mat.nb_nonzero());
+        let mut u_indptr = Vec::with_capacity(n + 1);
+        let mut u_indices = Vec::with_capacity(mat.nb_nonzero());
+        let mut u_data = Vec::with_capacity(mat.nb_nonzero());
+        l_indptr.push(0);
+        u_indptr.push(0);
+        for row_col in self.row_to_col.iter_mut() {
+            *row_col = None;
+        }
+
+        for k in 0..n {
+            let col_ind = self.symbolic.col_perm.at(k);
+            let col = mat.outer_view(col_ind).expect("col_ind not in bounds");
+            {
+                let l = csmat_borrowed_uchk(csmat::CSC, n, k,
+                                            &l_indptr, &l_indices, &l_data);
+                trisolve::lsolve_csc_sparse_rhs_perm(
+                    l, col, &self.row_to_col, &mut self.pattern_workspace,
+                    &mut self.x_workspace, &mut self.visited
+                ).expect("L has a unit diagonal");
+            }
+
+            // the already pivoted rows go into U, the others are candidates
+            // for the pivot, the diagonal row being preferred
+            let mut max_row = None;
+            let mut max_abs = N::zero();
+            let mut diag_abs = None;
+            for &row in self.pattern_workspace.iter_right()
+                                              .map(stack::extract_stack_val) {
+                self.visited[row] = false;
+                let x = self.x_workspace[row];
+                match self.row_to_col[row] {
+                    Some(j) => {
+                        u_indices.push(j);
+                        u_data.push(x);
+                    }
+                    None => {
+                        if max_row.is_none() || x.abs() > max_abs {
+                            max_row = Some(row);
+                            max_abs = x.abs();
+                        }
+                        if row == col_ind {
+                            diag_abs = Some(x.abs());
+                        }
+                    }
+                }
+            }
+            let pivot_row = match (max_row, diag_abs) {
+                (Some(_), _) if max_abs == N::zero() => None,
+                (Some(_), Some(diag_abs)) if diag_abs >= threshold * max_abs =>
+                    Some(col_ind),
+                (max_row, _) => max_row,
+            };
+            let pivot_row = match pivot_row {
+                Some(pivot_row) => pivot_row,
+                None => {
+                    self.pattern_workspace.clear_right();
+                    self.singular_pivot = Some(k);
+                    return Err(SprsError::SingularMatrix);
+                }
+            };
+
+            let pivot = self.x_workspace[pivot_row];
+            u_indices.push(k);
+            u_data.push(pivot);
+            u_indptr.push(u_indices.len());
+            l_indices.push(pivot_row);
+            l_data.push(N::one());
+            for &row in self.pattern_workspace.iter_right()
+                                              .map(stack::extract_stack_val) {
+                if row != pivot_row && self.row_to_col[row].is_none() {
+                    l_indices.push(row);
+                    l_data.push(self.x_workspace[row] / pivot);
+                }
+            }
+            l_indptr.push(l_indices.len());
+            self.row_to_col[pivot_row] = Some(k);
+            self.pattern_workspace.clear_right();
+        }
+
+        // L was built using the original row numbering
+        for row in l_indices.iter_mut() {
+            *row = self.row_to_col[*row].expect("all rows are pivoted");
+        }
+        let mut row_perm = vec![0; n];
+        for (row, row_col) in self.row_to_col.iter().enumerate() {
+            row_perm[row_col.expect("all rows are pivoted")] = row;
+        }
+        let keep_first = |x, _| x;
+        self.l = CsMat::new_owned_unsorted(csmat::CSC, n, n, l_indptr,
+                                           l_indices, l_data, keep_first)?;
+        self.u = CsMat::new_owned_unsorted(csmat::CSC, n, n, u_indptr,
+                                           u_indices, u_data, keep_first)?;
+        self.row_perm = Permutation::new(row_perm);
+        Ok(())
+    }
+
+    /// The index of the column without a nonzero pivot met by the last
+    /// update, in the column permuted ordering, if it failed with
+    /// `SingularMatrix`
+    pub fn singular_pivot(&self) -> Option<usize> {
+        self.singular_pivot
+    }
+
+    /// The unit lower triangular factor L
+    pub fn l(&self) -> CsMatView<'_, N>
+    where N: Copy {
+        self.l.borrowed()
+    }
+
+    /// The upper triangular factor U
+    pub fn u(&self) -> CsMatView<'_, N>
+    where N: Copy {
+        self.u.borrowed()
+    }
+
+    /// The row permutation P chosen by pivoting
+    pub fn row_perm(&self) -> &PermOwned {
+        &self.row_perm
+    }
+
+    /// The column permutation Q of the symbolic decomposition
+    pub fn col_perm(&self) -> &PermOwned {
+        &self.symbolic.col_perm
+    }
+
+    /// Number of nonzeros in the factors, including the unit diagonal of L
+    pub fn nnz(&self) -> usize
+    where N: Copy {
+        self.l.nb_nonzero() + self.u.nb_nonzero()
+    }
+
+    /// Solve the system A x = rhs
+    ///
+    /// # Panics
+    ///
+    /// If the dimension of rhs differs from the matrix's
+    pub fn solve<V>(&self, rhs: &V) -> Vec<N>
+    where N: Float,
+          V: Deref<Target = [N]>
+    {
+        assert_eq!(rhs.len(), self.symbolic.dim(), "dimension mismatch");
+        let mut x = &self.row_perm * &rhs[..];
+        trisolve::lsolve_csc_dense_rhs(self.l.borrowed(), &mut x)
+            .expect("L has a unit diagonal");
+        trisolve::usolve_csc_dense_rhs(self.u.borrowed(), &mut x)
+            .expect("U has nonzero pivots");
+        let qinv = self.symbolic.col_perm.inv();
+        &qinv * &x
+    }
+
+    /// Solve the system A^T x = rhs
+    ///
+    /// # Panics
+    ///
+    /// If the dimension of rhs differs from the matrix's
+    pub fn solve_transpose<V>(&self, rhs: &V) -> Vec<N>
+    where N: Float,
+          V: Deref<Target = [N]>
+    {
+        assert_eq!(rhs.len(), self.symbolic.dim(), "dimension mismatch");
+        let mut x = &self.symbolic.col_perm * &rhs[..];
+        trisolve::lsolve_csr_dense_rhs(self.u.transpose_view(), &mut x)
+            .expect("U has nonzero pivots");
+        trisolve::usolve_csr_dense_rhs(self.l.transpose_view(), &mut x)
+            .expect("L has a unit diagonal");
+        let pinv = self.row_perm.inv();
+        &pinv * &x
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sparse::csmat::{CsMat, CsMatOwned, CSC, CSR};
+    use sparse::permutation::Permutation;
+    use errors::SprsError;
+    use super::{LuNumeric, LuSymbolic};
+
+    fn unsymmetric_mat() -> CsMatOwned<f64> {
+        // |0 2 0 1 0|
+        // |3 0 0 0 1|
+        // |0 4 1 0 0|
+        // |1 0 0 0 2|
+        // |0 0 5 2 0|
+        CsMat::new_owned(CSR, 5, 5,
+                         vec![0, 2, 4, 6, 8, 10],
+                         vec![1, 3, 0, 4, 1, 2, 0, 4, 2, 3],
+                         vec![2., 1., 3., 1., 4., 1., 1., 2., 5., 2.])
+            .unwrap()
+    }
+
+    fn residual(mat: &CsMatOwned<f64>, x: &[f64], b: &[f64]) -> f64 {
+        let ax = (mat * &x.to_vec()).unwrap();
+        ax.iter().zip(b.iter()).map(|(a, b)| (a - b).abs()).fold(0., f64::max)
+    }
+
+    #[test]
+    fn lu_solve_with_pivoting() {
+        let mat = unsymmetric_mat();
+        let b = vec![1., 2., 3., 4., 5.];
+        let lu = LuNumeric::new(&mat).unwrap();
+        let x = lu.solve(&b);
+        assert!(residual(&mat, &x, &b) < 1e-12);
+
+        let x = lu.solve_transpose(&b);
+        let mat_t = mat.transpose_view().to_owned();
+        assert!(residual(&mat_t, &x, &b) < 1e-12);
+
+        // P A Q = L U, with a unit diagonal in L
+        assert!(lu.l().outer_iterator()
+                  .all(|(k, col)| col.indices()[0] == k
+                                  && col.data()[0] == 1.));
+        assert!(lu.u().outer_iterator()
+                  .all(|(k, col)| col.indices().last() == Some(&k)));
+
+        let col_perm = Permutation::new(vec![4, 2, 0, 3, 1]);
+        let lu = LuNumeric::new_perm(&mat.to_csc(), col_perm).unwrap();
+        let x = lu.solve(&b);
+        assert!(residual(&mat, &x, &b) < 1e-12);
+        let x = lu.solve_transpose(&b);
+        assert!(residual(&mat_t, &x, &b) < 1e-12);
+    }
+
+    #[test]
+    fn lu_pivot_threshold() {
+        // |1 1|
+        // |4 1|
+        let mat = CsMat::new_owned(CSC, 2, 2,
+                                   vec![0, 2, 4],
+                                   vec![0, 1, 0, 1],
+                                   vec![1., 4., 1., 1.]).unwrap();
+        let b = vec![2., 3.];
+
+        let lu = LuNumeric::new(&mat).unwrap();
+        assert_eq!(lu.row_perm().at(0), 1);
+        assert!(residual(&mat, &lu.solve(&b), &b) < 1e-12);
+
+        let mut symbolic = LuSymbolic::new(&mat).unwrap();
+        symbolic.set_pivot_threshold(0.1);
+        let mut lu = symbolic.factor(&mat).unwrap();
+        assert_eq!(lu.row_perm().at(0), 0);
+        assert!(residual(&mat, &lu.solve(&b), &b) < 1e-12);
+
+        let mat2 = CsMat::new_owned(CSC, 2, 2,
+                                    vec![0, 1, 3],
+                                    vec![1, 0, 1],
+                                    vec![2., 1., 1.]).unwrap();
+        lu.update(&mat2).unwrap();
+        assert!(residual(&mat2, &lu.solve(&b), &b) < 1e-12);
+    }
+
+    #[test]
+    fn lu_singular() {
+        // |1 2 0|
+        // |2 4 0|
+        // |0 0 1|
+        let mat = CsMat::new_owned(CSR, 3, 3,
+                                   vec![0, 2, 4, 5],
+                                   vec![0, 1, 0, 1, 2],
+                                   vec![1., 2., 2., 4., 1.]).unwrap();
+        let res = LuNumeric::new(&mat);
+        assert_eq!(res.unwrap_err(), SprsError::SingularMatrix);
+        let mut lu = LuNumeric::new(&CsMat::eye(CSR, 3)).unwrap();
+        assert_eq!(lu.update(&mat), Err(SprsError::SingularMatrix));
+        assert_eq!(lu.singular_pivot(), Some(1));
+        lu.update(&CsMat::eye(CSR, 3)).unwrap();
+        assert_eq!(lu.singular_pivot(), None);
+
+        let rect = CsMat::new_owned(CSR, 2, 3,
+                                    vec![0, 1, 2],
+                                    vec![0, 1],
+                                    vec![1., 1.]).unwrap();
+        assert_eq!(LuSymbolic::new(&rect).unwrap_err(),
+                   SprsError::NonSquareMatrix);
+    }
+}
@@ -7,6 +7,9 @@ use std::ops::{Add, Sub, Mul, Div};
 use std::iter::IntoIterator;
 
 pub mod cholesky;
+pub mod lu;
+pub mod qr;
+pub mod supernodal;
 pub mod trisolve;
 pub mod etree;
 
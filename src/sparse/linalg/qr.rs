@@ -0,0 +1,598 @@
+//! QR factorization of rectangular matrices
+//!
+//! This is a left looking Householder QR factorization, where the
+//! Householder vectors are stored in a sparse matrix V, such that
+//! H_k = I - beta_k v_k v_k^T and H_n ... H_1 P A Q = R. The nonzero
+//! patterns of V and R are predicted by the column elimination tree of A Q.
+
+use std::cmp;
+use std::default::Default;
+use std::ops::Deref;
+
+use num::traits::Float;
+
+use sparse::csmat::{self, CsMat, CsMatOwned, CsMatView};
+use sparse::permutation::{Permutation, PermOwned};
+use sparse::linalg::{etree, trisolve};
+use errors::SprsError;
+
+/// Structure holding the symbolic QR decomposition of a matrix
+///
+/// If the matrix has fewer rows than columns, its transpose is analyzed,
+/// which enables computing minimum norm solutions.
+#[derive(Debug)]
+pub struct QrSymbolic {
+    rows: usize,
+    cols: usize,
+    transposed: bool,
+    col_perm: PermOwned,
+    parents: etree::ParentsOwned,
+    leftmost: Vec<Option<usize>>,
+    row_perm: Vec<usize>,
+    rows_ext: usize,
+    v_nnz: usize,
+}
+
+/// Numeric QR decomposition of a matrix
+#[derive(Debug)]
+pub struct QrNumeric<N> {
+    symbolic: QrSymbolic,
+    v: CsMatOwned<N>,
+    beta: Vec<N>,
+    r: CsMatOwned<N>,
+    x_workspace: Vec<N>,
+    marks: Vec<Option<usize>>,
+    pattern_workspace: Vec<usize>,
+    singular_pivot: Option<usize>,
+}
+
+/// Get a CSC view of the matrix to factor, converting it if needed
+macro_rules! factored_csc {
+    ($mat: expr, $transposed: expr, $storage: ident) => {{
+        let mat = if $transposed {
+            $mat.transpose_view()
+        } else {
+            $mat.borrowed()
+        };
+        if mat.is_csc() {
+            mat
+        } else {
+            $storage = mat.to_csc();
+            $storage.borrowed()
+        }
+    }}
+}
+
+impl QrSymbolic {
+    /// Compute the symbolic QR decomposition of a matrix
+    pub fn new<N, IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>) -> QrSymbolic
+    where N: Copy + Default,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let perm: Permutation<Vec<usize>> = Permutation::identity();
+        QrSymbolic::new_perm(mat, perm)
+    }
+
+    /// Compute the symbolic QR decomposition of a matrix, after applying
+    /// the column permutation `col_perm` to the factored matrix.
+    ///
+    /// The factored matrix is the transpose of `mat` if it has fewer rows
+    /// than columns, in which case `col_perm` permutes the rows of `mat`.
+    /// A good column ordering is one which reduces the fill-in of the
+    /// Cholesky factorization of A^T A.
+    pub fn new_perm<N, IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>,
+                                    col_perm: PermOwned)
+                                    -> QrSymbolic
+    where N: Copy + Default,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let transposed = mat.rows() < mat.cols();
+        let mat_csc;
+        let mat = factored_csc!(mat, transposed, mat_csc);
+        let (m, n) = (mat.rows(), mat.cols());
+        let parents = etree::column_etree(mat.borrowed(), &col_perm)
+                          .expect("the factored matrix is CSC");
+
+        // leftmost[i] is the first column having a nonzero in row i
+        let mut leftmost = vec![None; m];
+        for k in (0..n).rev() {
+            let col = mat.outer_view(col_perm.at(k)).expect("k is in bounds");
+            for &row in col.indices() {
+                leftmost[row] = Some(k);
+            }
+        }
+
+        // Each row is first queued at its leftmost column. The first row
+        // of the queue of column k is the k-th row of R, while the other
+        // rows go into the pattern of the k-th Householder vector, and
+        // are then moved to the queue of the parent of k.
+        let mut next = vec![None; m];
+        let mut head = vec![None; n];
+        let mut tail = vec![None; n];
+        let mut queue_len = vec![0; n];
+        for row in (0..m).rev() {
+            if let Some(k) = leftmost[row] {
+                if queue_len[k] == 0 {
+                    tail[k] = Some(row);
+                }
+                queue_len[k] += 1;
+                next[row] = head[k];
+                head[k] = Some(row);
+            }
+        }
+        let mut row_perm = vec![None; m];
+        let mut rows_ext = m;
+        let mut v_nnz = 0;
+        for k in 0..n {
+            v_nnz += 1;
+            let row = match head[k] {
+                Some(row) => row,
+                None => {
+                    // structurally rank deficient, add a fictitious row
+                    rows_ext += 1;
+                    continue;
+                }
+            };
+            row_perm[row] = Some(k);
+            if queue_len[k] <= 1 {
+                continue;
+            }
+            queue_len[k] -= 1;
+            v_nnz += queue_len[k];
+            if let Some(parent) = parents.get_parent(k) {
+                if queue_len[parent] == 0 {
+                    tail[parent] = tail[k];
+                }
+                next[tail[k].expect("queue is not empty")] = head[parent];
+                head[parent] = next[row];
+                queue_len[parent] += queue_len[k];
+            }
+        }
+        let mut k = n;
+        let row_perm = row_perm.iter().map(|&row_k| {
+            row_k.unwrap_or_else(|| {
+                k += 1;
+                k - 1
+            })
+        }).collect();
+
+        QrSymbolic {
+            rows: if transposed { n } else { m },
+            cols: if transposed { m } else { n },
+            transposed,
+            col_perm,
+            parents,
+            leftmost,
+            row_perm,
+            rows_ext,
+            v_nnz,
+        }
+    }
+
+    /// The number of rows of the analyzed matrix
+    #[inline]
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The number of columns of the analyzed matrix
+    #[inline]
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Whether the transpose of the analyzed matrix is factored
+    #[inline]
+    pub fn is_transposed(&self) -> bool {
+        self.transposed
+    }
+
+    /// The number of nonzeros of the Householder vectors
+    #[inline]
+    pub fn v_nnz(&self) -> usize {
+        self.v_nnz
+    }
+
+    /// Compute the numeric decomposition of a matrix sharing the
+    /// nonzero pattern used to compute this symbolic decomposition
+    ///
+    /// # Errors
+    ///
+    /// * `IncompatibleDimensions` if the matrix shape differs from
+    ///   the symbolic decomposition's
+    /// * `SingularMatrix` if the factored matrix is rank deficient
+    pub fn factor<N, IpS, IS, DS>(self,
+                                  mat: &CsMat<N, IpS, IS, DS>)
+                                  -> Result<QrNumeric<N>, SprsError>
+    where N: Float + Default,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let n = cmp::min(self.rows, self.cols);
+        let rows_ext = self.rows_ext;
+        let empty = |rows| {
+            CsMat::new_owned(csmat::CSC, rows, n, vec![0; n + 1],
+                             Vec::new(), Vec::new())
+                .expect("empty matrix is valid")
+        };
+        let mut qr_numeric = QrNumeric {
+            symbolic: self,
+            v: empty(rows_ext),
+            beta: vec![N::zero(); n],
+            r: empty(n),
+            x_workspace: vec![N::zero(); rows_ext],
+            marks: vec![None; rows_ext],
+            pattern_workspace: vec![0; n],
+            singular_pivot: None,
+        };
+        qr_numeric.update(mat)?;
+        Ok(qr_numeric)
+    }
+}
+
+impl<N> QrNumeric<N> {
+
+    /// Compute the QR decomposition of a matrix
+    ///
+    /// # Errors
+    ///
+    /// See `QrSymbolic::factor`
+    pub fn new<IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>)
+                            -> Result<Self, SprsError>
+    where N: Float + Default,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        QrSymbolic::new(mat).factor(mat)
+    }
+
+    /// Compute the QR decomposition of a matrix, after applying the
+    /// column permutation `col_perm` to the factored matrix
+    ///
+    /// # Errors
+    ///
+    /// See `QrSymbolic::factor`
+    pub fn new_perm<IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>,
+                                 col_perm: PermOwned)
+                                 -> Result<Self, SprsError>
+    where N: Float + Default,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        QrSymbolic::new_perm(mat, col_perm).factor(mat)
+    }
+
+    /// Recompute the numeric decomposition for a matrix sharing the
+    /// nonzero pattern of the factored matrix
+    ///
+    /// If an error is returned, the decomposition is left in an
+    /// unspecified state and should not be used for solving until a
+    /// successful update.
+    ///
+    /// # Errors
+    ///
+    /// * `IncompatibleDimensions` if the matrix shape differs from
+    ///   the decomposition's
+    /// * `SingularMatrix` if the factored matrix is rank deficient, the
+    ///   index of the zero diagonal element of R in the permuted ordering
+    ///   is then given by `singular_pivot`
+    pub fn update<IpS, IS, DS>(&mut self, mat: &CsMat<N, IpS, IS, DS>)
+                               -> Result<(), SprsError>
+    where N: Float + Default,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        self.singular_pivot = None;
+        if mat.rows() != self.symbolic.rows
+           || mat.cols() != self.symbolic.cols {
+            return Err(SprsError::IncompatibleDimensions);
+        }
+        let mat_csc;
+        let mat = factored_csc!(mat, self.symbolic.transposed, mat_csc);
+        let n = mat.cols();
+        let rows_ext = self.symbolic.rows_ext;
+        let parents = &self.symbolic.parents;
+        let x = &mut self.x_workspace;
+        let marks = &mut self.marks;
+        let stack = &mut self.pattern_workspace;
+        for mark in marks.iter_mut() {
+            *mark = None;
+        }
+
+        let mut v_indptr = Vec::with_capacity(n + 1);
+        let mut v_indices = Vec::with_capacity(self.symbolic.v_nnz);
+        let mut v_data = Vec::with_capacity(self.symbolic.v_nnz);
+        let mut r_indptr = Vec::with_capacity(n + 1);
+        let mut r_indices = Vec::with_capacity(mat.nb_nonzero());
+        let mut r_data = Vec::with_capacity(mat.nb_nonzero());
+        v_indptr.push(0);
+        r_indptr.push(0);
+        let mut path = Vec::new();
+
+        for k in 0..n {
+            let v_start = v_indices.len();
+            marks[k] = Some(k);
+            v_indices.push(k);
+            let col_ind = self.symbolic.col_perm.at(k);
+            let col = mat.outer_view(col_ind).expect("col_ind in bounds");
+            // the pattern of R(:, k) is the union of the paths from the
+            // leftmost columns of the rows of A(:, k) to k in the etree
+            let mut top = n;
+            for (row, val) in col.iter() {
+                let mut node = self.symbolic.leftmost[row]
+                                   .expect("row is not empty");
+                path.clear();
+                while marks[node] != Some(k) {
+                    path.push(node);
+                    marks[node] = Some(k);
+                    node = parents.get_parent(node)
+                                  .expect("k is an ancestor of node");
+                }
+                for &node in path.iter().rev() {
+                    top -= 1;
+                    stack[top] = node;
+                }
+                let i = self.symbolic.row_perm[row];
+                x[i] = val;
+                if i > k && marks[i] != Some(k) {
+                    v_indices.push(i);
+                    marks[i] = Some(k);
+                }
+            }
+            for &i in &stack[top..n] {
+                let v_range = v_indptr[i]..v_indptr[i + 1];
+                happly(&v_indices[v_range.clone()], &v_data[v_range.clone()],
+                       self.beta[i], x);
+                r_indices.push(i);
+                r_data.push(x[i]);
+                x[i] = N::zero();
+                if parents.get_parent(i) == Some(k) {
+                    for ind in v_range {
+                        let row = v_indices[ind];
+                        if marks[row] != Some(k) {
+                            marks[row] = Some(k);
+                            v_indices.push(row);
+                        }
+                    }
+                }
+            }
+            for &row in &v_indices[v_start..] {
+                v_data.push(x[row]);
+                x[row] = N::zero();
+            }
+            let (beta, norm) = house(&mut v_data[v_start..]);
+            if norm == N::zero() {
+                self.singular_pivot = Some(k);
+                return Err(SprsError::SingularMatrix);
+            }
+            self.beta[k] = beta;
+            r_indices.push(k);
+            r_data.push(norm);
+            v_indptr.push(v_indices.len());
+            r_indptr.push(r_indices.len());
+        }
+
+        let keep_first = |x, _| x;
+        self.v = CsMat::new_owned_unsorted(csmat::CSC, rows_ext, n, v_indptr,
+                                           v_indices, v_data, keep_first)?;
+        self.r = CsMat::new_owned_unsorted(csmat::CSC, n, n, r_indptr,
+                                           r_indices, r_data, keep_first)?;
+        Ok(())
+    }
+
+    /// The index of the zero diagonal element of R met by the last
+    /// update, in the permuted ordering, if it failed with
+    /// `SingularMatrix`
+    pub fn singular_pivot(&self) -> Option<usize> {
+        self.singular_pivot
+    }
+
+    /// The upper triangular factor R
+    pub fn r(&self) -> CsMatView<'_, N>
+    where N: Copy {
+        self.r.borrowed()
+    }
+
+    /// The column permutation of the factored matrix
+    pub fn col_perm(&self) -> &PermOwned {
+        &self.symbolic.col_perm
+    }
+
+    /// Solve the linear least squares problem min ||A x - rhs||
+    ///
+    /// If A has fewer rows than columns, the system is underdetermined
+    /// and its minimum norm solution is returned instead, ie the solution
+    /// of min ||x|| such that A x = rhs.
+    ///
+    /// # Panics
+    ///
+    /// If the dimension of rhs differs from the number of rows of A
+    pub fn solve_least_squares<V>(&self, rhs: &V) -> Vec<N>
+    where N: Float,
+          V: Deref<Target = [N]>
+    {
+        assert_eq!(rhs.len(), self.symbolic.rows, "dimension mismatch");
+        if self.symbolic.transposed {
+            self.solve_min_norm(rhs)
+        } else {
+            self.solve_overdetermined(rhs)
+        }
+    }
+
+    /// A = Q R P^T, thus x = P R^-1 Q^T b
+    fn solve_overdetermined(&self, rhs: &[N]) -> Vec<N>
+    where N: Float {
+        let n = self.symbolic.cols;
+        let mut x = vec![N::zero(); self.symbolic.rows_ext];
+        for (&val, &row) in rhs.iter().zip(self.symbolic.row_perm.iter()) {
+            x[row] = val;
+        }
+        self.apply_householder(&mut x, 0..n);
+        trisolve::usolve_csc_dense_rhs(self.r.borrowed(), &mut x[..n])
+            .expect("R has a nonzero diagonal");
+        &self.symbolic.col_perm.inv() * &x[..n]
+    }
+
+    /// A^T = Q R P^T, thus A = P R^T Q^T and x = Q R^-T P^T b
+    fn solve_min_norm(&self, rhs: &[N]) -> Vec<N>
+    where N: Float {
+        let n = self.symbolic.rows;
+        let mut x = &self.symbolic.col_perm * rhs;
+        trisolve::lsolve_csr_dense_rhs(self.r.transpose_view(), &mut x)
+            .expect("R has a nonzero diagonal");
+        x.resize(self.symbolic.rows_ext, N::zero());
+        self.apply_householder(&mut x, (0..n).rev());
+        self.symbolic.row_perm.iter().map(|&row| x[row]).collect()
+    }
+
+    fn apply_householder<I>(&self, x: &mut [N], order: I)
+    where N: Float,
+          I: Iterator<Item = usize>
+    {
+        for k in order {
+            let v = self.v.outer_view(k).expect("k is in bounds");
+            happly(v.indices(), v.data(), self.beta[k], x);
+        }
+    }
+}
+
+/// Apply the Householder reflection I - beta v v^T to x
+fn happly<N: Float>(v_indices: &[usize], v_data: &[N], beta: N, x: &mut [N]) {
+    let tau = v_indices.iter()
+                       .zip(v_data.iter())
+                       .fold(N::zero(), |acc, (&i, &v)| acc + v * x[i]);
+    let tau = tau * beta;
+    for (&i, &v) in v_indices.iter().zip(v_data.iter()) {
+        x[i] = x[i] - v * tau;
+    }
+}
+
+/// Compute the Householder reflection I - beta v v^T mapping x to
+/// norm(x) e_1 (up to the sign), overwriting x with v.
+/// Returns beta and the norm of x.
+fn house<N: Float>(x: &mut [N]) -> (N, N) {
+    let sigma = x[1..].iter().fold(N::zero(), |acc, &v| acc + v * v);
+    let two = N::one() + N::one();
+    if sigma == N::zero() {
+        let norm = x[0].abs();
+        let beta = if x[0] <= N::zero() { two } else { N::zero() };
+        x[0] = N::one();
+        (beta, norm)
+    } else {
+        let norm = (x[0] * x[0] + sigma).sqrt();
+        x[0] = if x[0] <= N::zero() {
+            x[0] - norm
+        } else {
+            -sigma / (x[0] + norm)
+        };
+        (-N::one() / (norm * x[0]), norm)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sparse::csmat::{CsMat, CsMatOwned, CSR};
+    use sparse::permutation::Permutation;
+    use errors::SprsError;
+    use super::{QrNumeric, QrSymbolic};
+
+    fn overdetermined_mat() -> CsMatOwned<f64> {
+        // |1 0 0 2|
+        // |0 3 0 0|
+        // |4 0 1 0|
+        // |0 0 0 0|
+        // |0 1 0 5|
+        // |0 0 2 1|
+        CsMat::new_owned(CSR, 6, 4,
+                         vec![0, 2, 3, 5, 5, 7, 9],
+                         vec![0, 3, 1, 0, 2, 1, 3, 2, 3],
+                         vec![1., 2., 3., 4., 1., 1., 5., 2., 1.]).unwrap()
+    }
+
+    fn max_abs(x: &[f64]) -> f64 {
+        x.iter().map(|v| v.abs()).fold(0., f64::max)
+    }
+
+    #[test]
+    fn least_squares() {
+        let mat = overdetermined_mat();
+        let b = vec![1., 2., 3., 4., 5., 6.];
+        let check = |x: &[f64]| {
+            // the residual is orthogonal to the range of A
+            let res: Vec<f64> = (&mat * &x.to_vec()).unwrap()
+                                                     .iter()
+                                                     .zip(b.iter())
+                                                     .map(|(ax, b)| ax - b)
+                                                     .collect();
+            let mat_t = mat.transpose_view();
+            let normal_res = (&mat_t * &res).unwrap();
+            assert!(max_abs(&normal_res) < 1e-12);
+        };
+
+        let qr = QrNumeric::new(&mat).unwrap();
+        let x = qr.solve_least_squares(&b);
+        assert_eq!(x.len(), 4);
+        check(&x);
+
+        let col_perm = Permutation::new(vec![3, 1, 0, 2]);
+        let mut qr = QrNumeric::new_perm(&mat.to_csc(), col_perm).unwrap();
+        check(&qr.solve_least_squares(&b));
+
+        let mat2 = mat.map(|x| 2. * x);
+        qr.update(&mat2).unwrap();
+        let x2: Vec<f64> = qr.solve_least_squares(&b).iter()
+                                                     .map(|x| 2. * x)
+                                                     .collect();
+        check(&x2);
+    }
+
+    #[test]
+    fn min_norm() {
+        // |1 1 0|
+        // |0 1 1|
+        let mat = CsMat::new_owned(CSR, 2, 3,
+                                   vec![0, 2, 4],
+                                   vec![0, 1, 1, 2],
+                                   vec![1., 1., 1., 1.]).unwrap();
+        let symbolic = QrSymbolic::new(&mat);
+        assert!(symbolic.is_transposed());
+        let qr = symbolic.factor(&mat).unwrap();
+        let x = qr.solve_least_squares(&vec![1., 1.]);
+        let expected: [f64; 3] = [1. / 3., 2. / 3., 1. / 3.];
+        for (x, e) in x.iter().zip(expected.iter()) {
+            assert!((x - e).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn rank_deficient() {
+        // |1 1|
+        // |0 0|
+        // |0 0|
+        let mat = CsMat::new_owned(CSR, 3, 2,
+                                   vec![0, 2, 2, 2],
+                                   vec![0, 1],
+                                   vec![1., 1.]).unwrap();
+        let res = QrNumeric::new(&mat);
+        assert_eq!(res.unwrap_err(), SprsError::SingularMatrix);
+        let full_rank = CsMat::new_owned(CSR, 3, 2,
+                                         vec![0, 2, 3, 3],
+                                         vec![0, 1, 1],
+                                         vec![1., 1., 1.]).unwrap();
+        let mut qr = QrNumeric::new(&full_rank).unwrap();
+        assert_eq!(qr.update(&mat), Err(SprsError::SingularMatrix));
+        assert_eq!(qr.singular_pivot(), Some(1));
+
+        let mut qr = QrNumeric::new(&overdetermined_mat()).unwrap();
+        let res = qr.update(&mat);
+        assert_eq!(res.unwrap_err(), SprsError::IncompatibleDimensions);
+    }
+}
@@ -0,0 +1,705 @@
+//! Supernodal LDLT factorization
+//!
+//! A supernode is a set of consecutive columns of L sharing the same
+//! nonzero structure below the diagonal. Storing each supernode as a dense
+//! block enables performing the factorization using dense kernels, which
+//! is much more efficient than the scalar up-looking factorization when
+//! the factors have large dense parts, as is the case for 3D problems.
+//!
+//! The columns are reordered by a postorder of the elimination tree, which
+//! does not change the fill-in but ensures the columns of the supernodes
+//! are consecutive.
+
+use std::ops::{Deref, Range};
+
+use num::traits::Num;
+use std::ops::{Add, Sub, Mul, Div};
+
+use sparse::csmat::CsMat;
+use sparse::permutation::{Permutation, PermOwned};
+use sparse::linalg::{self, etree};
+use sparse::linalg::cholesky::{ldl_symbolic, SymmetryCheck};
+use errors::SprsError;
+
+/// Structure holding the symbolic supernodal LDLT decomposition
+#[derive(Debug)]
+pub struct SupernodalLdlSymbolic {
+    perm: PermOwned,
+    parents: etree::ParentsOwned,
+    nnz: usize,
+    /// first column of each supernode, followed by n
+    sn_cols: Vec<usize>,
+    /// supernode of each column
+    col_to_sn: Vec<usize>,
+    /// offsets of the row structure of each supernode into sn_rows
+    sn_rows_ptr: Vec<usize>,
+    /// row structure of the supernodes, including their diagonal block
+    sn_rows: Vec<usize>,
+    /// offsets of the dense block of each supernode into the values
+    sn_values_ptr: Vec<usize>,
+}
+
+/// Numeric supernodal LDLT decomposition of a symmetric matrix
+#[derive(Debug)]
+pub struct SupernodalLdlNumeric<N> {
+    symbolic: SupernodalLdlSymbolic,
+    values: Vec<N>,
+    diag: Vec<N>,
+    row_map: Vec<usize>,
+    update_workspace: Vec<N>,
+    singular_pivot: Option<usize>,
+}
+
+impl SupernodalLdlSymbolic {
+    /// Compute the symbolic decomposition of a symmetric matrix
+    ///
+    /// # Errors
+    ///
+    /// * `NonSquareMatrix` if the matrix is not square
+    /// * `NonSymmetricMatrix` if the matrix is not symmetric
+    pub fn new<N, IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>)
+                               -> Result<SupernodalLdlSymbolic, SprsError>
+    where N: Copy + PartialEq,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let perm: Permutation<Vec<usize>> = Permutation::identity();
+        SupernodalLdlSymbolic::new_perm(mat, perm)
+    }
+
+    /// Compute the symbolic decomposition of a symmetric matrix, after
+    /// applying the symmetric permutation `perm`
+    ///
+    /// The permutation is then composed with a postorder of the
+    /// elimination tree, thus `perm` should be a fill reducing ordering.
+    ///
+    /// # Errors
+    ///
+    /// * `NonSquareMatrix` if the matrix is not square
+    /// * `NonSymmetricMatrix` if the matrix is not symmetric
+    pub fn new_perm<N, IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>,
+                                    perm: PermOwned)
+                                    -> Result<SupernodalLdlSymbolic,
+                                              SprsError>
+    where N: Copy + PartialEq,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let n = mat.cols();
+        if mat.rows() != n {
+            return Err(SprsError::NonSquareMatrix);
+        }
+        let mut l_colptr = vec![0; n + 1];
+        let mut parents = etree::ParentsOwned::new(n);
+        let mut l_nz = vec![0; n];
+        let mut flag_workspace = vec![0; n];
+        ldl_symbolic(mat.borrowed(),
+                     &perm,
+                     &mut l_colptr,
+                     parents.borrowed_mut(),
+                     &mut l_nz,
+                     &mut flag_workspace,
+                     SymmetryCheck::CheckSymmetry)?;
+
+        // the postordered etree has the same fill-in, but the chains of
+        // the tree are numbered consecutively
+        let post = parents.postorder();
+        let perm = Permutation::new(post.iter()
+                                        .map(|&k| perm.at(k))
+                                        .collect());
+        ldl_symbolic(mat.borrowed(),
+                     &perm,
+                     &mut l_colptr,
+                     parents.borrowed_mut(),
+                     &mut l_nz,
+                     &mut flag_workspace,
+                     SymmetryCheck::DontCheckSymmetry)?;
+
+        // a column joins the supernode of its predecessor if it is its
+        // parent and the structure of the predecessor is the structure
+        // of the column plus the column itself
+        let mut sn_cols = Vec::new();
+        let mut col_to_sn = Vec::with_capacity(n);
+        for k in 0..n {
+            if k == 0 || parents.get_parent(k - 1) != Some(k)
+                      || l_nz[k - 1] != l_nz[k] + 1 {
+                sn_cols.push(k);
+            }
+            col_to_sn.push(sn_cols.len() - 1);
+        }
+        sn_cols.push(n);
+        let nb_sn = sn_cols.len() - 1;
+        let mut sn_rows_ptr = Vec::with_capacity(nb_sn + 1);
+        let mut sn_values_ptr = Vec::with_capacity(nb_sn + 1);
+        sn_rows_ptr.push(0);
+        sn_values_ptr.push(0);
+        for sn in 0..nb_sn {
+            let (first, last) = (sn_cols[sn], sn_cols[sn + 1]);
+            let nrows = l_nz[first] + 1;
+            let rows_end = sn_rows_ptr[sn] + nrows;
+            let values_end = sn_values_ptr[sn] + nrows * (last - first);
+            sn_rows_ptr.push(rows_end);
+            sn_values_ptr.push(values_end);
+        }
+
+        // the structure of the first column of each supernode is obtained
+        // by traversing the row subtrees of L, as the k-th row of L is the
+        // set of nodes reachable from the nonzeros of the k-th row of A
+        let mut sn_rows = vec![0; sn_rows_ptr[nb_sn]];
+        let mut sn_len = vec![1; nb_sn];
+        for (sn, &first) in sn_cols[..nb_sn].iter().enumerate() {
+            sn_rows[sn_rows_ptr[sn]] = first;
+        }
+        for k in 0..n {
+            flag_workspace[k] = k;
+            let col = mat.outer_view(perm.at(k)).expect("k is in bounds");
+            for &row in col.indices() {
+                let mut i = perm.at_inv(row);
+                while i < k && flag_workspace[i] != k {
+                    flag_workspace[i] = k;
+                    let sn = col_to_sn[i];
+                    if sn_cols[sn] == i {
+                        sn_rows[sn_rows_ptr[sn] + sn_len[sn]] = k;
+                        sn_len[sn] += 1;
+                    }
+                    i = parents.get_parent(i).expect("k is an ancestor of i");
+                }
+            }
+        }
+
+        Ok(SupernodalLdlSymbolic {
+            perm,
+            parents,
+            nnz: l_colptr[n],
+            sn_cols,
+            col_to_sn,
+            sn_rows_ptr,
+            sn_rows,
+            sn_values_ptr,
+        })
+    }
+
+    /// The dimension of the factored matrix
+    #[inline]
+    pub fn dim(&self) -> usize {
+        self.parents.nb_nodes()
+    }
+
+    /// The number of nonzeros of L, excluding its unit diagonal
+    #[inline]
+    pub fn nnz(&self) -> usize {
+        self.nnz
+    }
+
+    /// The number of supernodes
+    #[inline]
+    pub fn nb_supernodes(&self) -> usize {
+        self.sn_cols.len() - 1
+    }
+
+    /// The permutation applied to the factored matrix, including the
+    /// postordering of the elimination tree
+    pub fn perm(&self) -> &PermOwned {
+        &self.perm
+    }
+
+    /// The row structure of a supernode
+    fn sn_rows(&self, sn: usize) -> &[usize] {
+        &self.sn_rows[self.sn_rows_ptr[sn]..self.sn_rows_ptr[sn + 1]]
+    }
+
+    /// Compute the numeric decomposition of a matrix sharing the
+    /// nonzero pattern used to compute this symbolic decomposition
+    ///
+    /// # Errors
+    ///
+    /// * `IncompatibleDimensions` if the matrix dimension differs from
+    ///   the symbolic decomposition's
+    /// * `SingularMatrix` if a zero pivot is met
+    pub fn factor<N, IpS, IS, DS>(self,
+                                  mat: &CsMat<N, IpS, IS, DS>)
+                                  -> Result<SupernodalLdlNumeric<N>, SprsError>
+    where N: Copy + Num + PartialOrd,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let n = self.dim();
+        let nb_values = self.sn_values_ptr[self.nb_supernodes()];
+        let mut ldl_numeric = SupernodalLdlNumeric {
+            symbolic: self,
+            values: vec![N::zero(); nb_values],
+            diag: vec![N::zero(); n],
+            row_map: vec![0; n],
+            update_workspace: Vec::new(),
+            singular_pivot: None,
+        };
+        ldl_numeric.update(mat)?;
+        Ok(ldl_numeric)
+    }
+}
+
+impl<N> SupernodalLdlNumeric<N> {
+
+    /// Compute the supernodal LDLT decomposition of a symmetric matrix
+    ///
+    /// # Errors
+    ///
+    /// See `SupernodalLdlSymbolic::new` and `SupernodalLdlSymbolic::factor`
+    pub fn new<IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>)
+                            -> Result<Self, SprsError>
+    where N: Copy + Num + PartialOrd,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let symbolic = SupernodalLdlSymbolic::new(mat)?;
+        symbolic.factor(mat)
+    }
+
+    /// Compute the supernodal LDLT decomposition of a symmetric matrix,
+    /// after applying the symmetric permutation `perm`
+    ///
+    /// # Errors
+    ///
+    /// See `SupernodalLdlSymbolic::new_perm` and
+    /// `SupernodalLdlSymbolic::factor`
+    pub fn new_perm<IpS, IS, DS>(mat: &CsMat<N, IpS, IS, DS>,
+                                 perm: PermOwned)
+                                 -> Result<Self, SprsError>
+    where N: Copy + Num + PartialOrd,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        let symbolic = SupernodalLdlSymbolic::new_perm(mat, perm)?;
+        symbolic.factor(mat)
+    }
+
+    /// Recompute the numeric decomposition for a matrix sharing the
+    /// nonzero pattern of the factored matrix
+    ///
+    /// The nonzero pattern of `mat` should be included in the pattern of
+    /// the factored matrix. Otherwise, the resulting decomposition is
+    /// unspecified, and this method may panic. This is checked by a debug
+    /// assertion.
+    ///
+    /// If an error is returned, the decomposition is left in an
+    /// unspecified state and should not be used for solving until a
+    /// successful update.
+    ///
+    /// # Errors
+    ///
+    /// * `IncompatibleDimensions` if the matrix dimension differs from
+    ///   the decomposition's
+    /// * `SingularMatrix` if a zero pivot is met, its index is then
+    ///   given by `singular_pivot`
+    pub fn update<IpS, IS, DS>(&mut self, mat: &CsMat<N, IpS, IS, DS>)
+                               -> Result<(), SprsError>
+    where N: Copy + Num + PartialOrd,
+          IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [N]>
+    {
+        self.singular_pivot = None;
+        let n = self.symbolic.dim();
+        if mat.rows() != n || mat.cols() != n {
+            return Err(SprsError::IncompatibleDimensions);
+        }
+        let symbolic = &self.symbolic;
+        let nb_sn = symbolic.nb_supernodes();
+        // descendants of the supernodes which are still to be applied, as
+        // linked lists of the supernodes whose next row to apply belongs
+        // to a given supernode
+        let mut head = vec![None; nb_sn];
+        let mut next = vec![None; nb_sn];
+        let mut next_row = vec![0; nb_sn];
+
+        for sn in 0..nb_sn {
+            let (first, last) = (symbolic.sn_cols[sn], symbolic.sn_cols[sn + 1]);
+            let ncols = last - first;
+            let rows = symbolic.sn_rows(sn);
+            let nrows = rows.len();
+            for (loc, &row) in rows.iter().enumerate() {
+                self.row_map[row] = loc;
+            }
+            let (done, todo) = self.values
+                                   .split_at_mut(symbolic.sn_values_ptr[sn]);
+            let block = &mut todo[..nrows * ncols];
+
+            // scatter the lower part of the columns of A
+            for val in block.iter_mut() {
+                *val = N::zero();
+            }
+            for k in first..last {
+                let col = mat.outer_view(symbolic.perm.at(k))
+                             .expect("k is in bounds");
+                let block_col = &mut block[(k - first) * nrows..];
+                for (row, val) in col.iter() {
+                    let i = symbolic.perm.at_inv(row);
+                    if i >= k {
+                        let loc = self.row_map[i];
+                        debug_assert!(rows.get(loc) == Some(&i),
+                                      "entry outside of the symbolic pattern");
+                        block_col[loc] = block_col[loc] + val;
+                    }
+                }
+            }
+
+            // apply the updates of the descendants
+            let mut desc = head[sn];
+            while let Some(desc_sn) = desc {
+                desc = next[desc_sn];
+                let desc_first = symbolic.sn_cols[desc_sn];
+                let desc_ncols = symbolic.sn_cols[desc_sn + 1] - desc_first;
+                let desc_rows = symbolic.sn_rows(desc_sn);
+                let start = next_row[desc_sn];
+                let end = start + desc_rows[start..].iter()
+                                                   .take_while(|&&r| r < last)
+                                                   .count();
+                let desc_start = symbolic.sn_values_ptr[desc_sn];
+                let desc = Descendant {
+                    block: &done[desc_start
+                                 ..desc_start + desc_rows.len() * desc_ncols],
+                    rows: desc_rows,
+                    diag: &self.diag[desc_first..desc_first + desc_ncols],
+                };
+                supernode_update(desc,
+                                 start..end,
+                                 block,
+                                 nrows,
+                                 first,
+                                 &self.row_map,
+                                 &mut self.update_workspace);
+                next_row[desc_sn] = end;
+                if end < desc_rows.len() {
+                    let anc_sn = symbolic.col_to_sn[desc_rows[end]];
+                    next[desc_sn] = head[anc_sn];
+                    head[anc_sn] = Some(desc_sn);
+                }
+            }
+
+            let panel_diag = &mut self.diag[first..last];
+            if let Err(j) = dense_ldl_panel(block, nrows, ncols, panel_diag) {
+                self.singular_pivot = Some(first + j);
+                return Err(SprsError::SingularMatrix);
+            }
+
+            if nrows > ncols {
+                next_row[sn] = ncols;
+                let anc_sn = symbolic.col_to_sn[rows[ncols]];
+                next[sn] = head[anc_sn];
+                head[anc_sn] = Some(sn);
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of supernodes
+    #[inline]
+    pub fn nb_supernodes(&self) -> usize {
+        self.symbolic.nb_supernodes()
+    }
+
+    /// The index of the zero pivot met by the last update, in the
+    /// permuted ordering, if it failed with `SingularMatrix`
+    pub fn singular_pivot(&self) -> Option<usize> {
+        self.singular_pivot
+    }
+
+    /// Solve the system A x = rhs, where A is the factored matrix
+    pub fn solve<'a, V, M>(&self, rhs: &V) -> Vec<M>
+    where N: 'a + Copy + Num,
+          V: Deref<Target = [M]>,
+          M: 'a + Copy + Add<Output=M> + Sub<Output=M> + Mul<N, Output=M> + Div<N, Output=M>
+    {
+        let symbolic = &self.symbolic;
+        let mut x = &symbolic.perm * &rhs[..];
+        let nb_sn = symbolic.nb_supernodes();
+        for sn in 0..nb_sn {
+            let first = symbolic.sn_cols[sn];
+            let rows = symbolic.sn_rows(sn);
+            for (j, col) in self.sn_block(sn).chunks(rows.len()).enumerate() {
+                let x_col = x[first + j];
+                for (&row, &val) in rows[j + 1..].iter().zip(&col[j + 1..]) {
+                    x[row] = x[row] - x_col * val;
+                }
+            }
+        }
+        linalg::diag_solve(&self.diag, &mut x);
+        for sn in (0..nb_sn).rev() {
+            let first = symbolic.sn_cols[sn];
+            let rows = symbolic.sn_rows(sn);
+            for (j, col) in self.sn_block(sn).chunks(rows.len())
+                                             .enumerate()
+                                             .rev() {
+                let mut x_col = x[first + j];
+                for (&row, &val) in rows[j + 1..].iter().zip(&col[j + 1..]) {
+                    x_col = x_col - x[row] * val;
+                }
+                x[first + j] = x_col;
+            }
+        }
+        let pinv = symbolic.perm.inv();
+        &pinv * &x
+    }
+
+    /// The dense column major block of L of a supernode
+    fn sn_block(&self, sn: usize) -> &[N] {
+        let range = self.symbolic.sn_values_ptr[sn]
+                    ..self.symbolic.sn_values_ptr[sn + 1];
+        &self.values[range]
+    }
+}
+
+/// Dense LDLT factorization of the column major panel of a supernode,
+/// whose top square block is symmetric. The strictly lower part of the
+/// panel is overwritten with L, while its diagonal is set to 1 and the
+/// diagonal of D is written into diag.
+///
+/// Returns the index of the column with a zero pivot on failure.
+fn dense_ldl_panel<N>(panel: &mut [N],
+                      nrows: usize,
+                      ncols: usize,
+                      diag: &mut [N])
+                      -> Result<(), usize>
+where N: Copy + Num
+{
+    for j in 0..ncols {
+        let (left, right) = panel.split_at_mut((j + 1) * nrows);
+        let col = &mut left[j * nrows..];
+        let d = col[j];
+        if d == N::zero() {
+            return Err(j);
+        }
+        diag[j] = d;
+        col[j] = N::one();
+        for val in &mut col[j + 1..] {
+            *val = *val / d;
+        }
+        for (jj, next_col) in right.chunks_mut(nrows)
+                                   .enumerate()
+                                   .map(|(jj, c)| (jj + j + 1, c)) {
+            let l_jj = col[jj] * d;
+            for (val, &l) in next_col[jj..].iter_mut().zip(&col[jj..]) {
+                *val = *val - l * l_jj;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The factored part of a descendant supernode updating a panel
+struct Descendant<'a, N: 'a> {
+    /// dense column major block of L
+    block: &'a [N],
+    /// row structure
+    rows: &'a [usize],
+    /// diagonal of D
+    diag: &'a [N],
+}
+
+/// Subtract from the panel of a supernode the contribution of one of its
+/// descendants, ie L_D[r, :] D_D L_D[c, :]^T for the rows r of the
+/// descendant starting at cols.start, and its rows c in cols, which are
+/// the columns of the supernode updated by the descendant.
+///
+/// The update is first computed as a dense column major block in the
+/// workspace, only its lower trapezoidal part being needed, then scattered
+/// into the panel.
+fn supernode_update<N>(desc: Descendant<N>,
+                       cols: Range<usize>,
+                       panel: &mut [N],
+                       nrows: usize,
+                       first: usize,
+                       row_map: &[usize],
+                       workspace: &mut Vec<N>)
+where N: Copy + Num
+{
+    let desc_nrows = desc.rows.len();
+    let ncols = cols.end - cols.start;
+    let m = desc_nrows - cols.start;
+    workspace.clear();
+    workspace.resize(m * ncols, N::zero());
+    // column major rank ncols update, one column of the descendant at a time
+    for (desc_col, &d) in desc.block.chunks(desc_nrows).zip(desc.diag) {
+        let desc_col = &desc_col[cols.start..];
+        for (c, update_col) in workspace.chunks_mut(m).enumerate() {
+            let l_cd = desc_col[c] * d;
+            if l_cd == N::zero() {
+                continue;
+            }
+            for (val, &l) in update_col[c..].iter_mut().zip(&desc_col[c..]) {
+                *val = *val + l * l_cd;
+            }
+        }
+    }
+    let rows = &desc.rows[cols.start..];
+    for (c, update_col) in workspace.chunks(m).enumerate() {
+        let panel_col = &mut panel[(rows[c] - first) * nrows..];
+        for (&row, &val) in rows[c..].iter().zip(&update_col[c..]) {
+            let loc = row_map[row];
+            panel_col[loc] = panel_col[loc] - val;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sparse::csmat::{CsMat, CsMatOwned, CSC, CSR};
+    use sparse::permutation::Permutation;
+    use sparse::linalg::cholesky::LdlNumeric;
+    use errors::SprsError;
+    use super::{SupernodalLdlNumeric, SupernodalLdlSymbolic};
+
+    /// The 7 points laplacian on a cubic grid of side n, with a shift
+    fn laplacian_3d(n: usize) -> CsMatOwned<f64> {
+        let ind = |i: usize, j: usize, k: usize| (i * n + j) * n + k;
+        let mut indptr = vec![0];
+        let mut indices = Vec::new();
+        let mut data = Vec::new();
+        for i in 0..n {
+            for j in 0..n {
+                for k in 0..n {
+                    let mut row = vec![(ind(i, j, k), 6.5)];
+                    if i > 0 { row.push((ind(i - 1, j, k), -1.)); }
+                    if i < n - 1 { row.push((ind(i + 1, j, k), -1.)); }
+                    if j > 0 { row.push((ind(i, j - 1, k), -1.)); }
+                    if j < n - 1 { row.push((ind(i, j + 1, k), -1.)); }
+                    if k > 0 { row.push((ind(i, j, k - 1), -1.)); }
+                    if k < n - 1 { row.push((ind(i, j, k + 1), -1.)); }
+                    row.sort_by_key(|&(col, _)| col);
+                    indices.extend(row.iter().map(|&(col, _)| col));
+                    data.extend(row.iter().map(|&(_, val)| val));
+                    indptr.push(indices.len());
+                }
+            }
+        }
+        CsMat::new_owned(CSR, n * n * n, n * n * n, indptr, indices, data)
+            .unwrap()
+    }
+
+    #[test]
+    fn supernodal_solve() {
+        let mat = laplacian_3d(4);
+        let n = mat.rows();
+        let b: Vec<f64> = (0..n).map(|i| (i % 7) as f64 - 3.).collect();
+        let ldlt = SupernodalLdlNumeric::new(&mat).unwrap();
+        assert!(ldlt.nb_supernodes() < n);
+        let x = ldlt.solve(&b);
+        let x0 = LdlNumeric::new(&mat).unwrap().solve(&b);
+        for (x, x0) in x.iter().zip(x0.iter()) {
+            assert!((x - x0).abs() < 1e-12);
+        }
+
+        // reversed ordering, with a CSC matrix
+        let perm = Permutation::new((0..n).rev().collect());
+        let mut ldlt = SupernodalLdlNumeric::new_perm(&mat.to_csc(), perm)
+                           .unwrap();
+        let x = ldlt.solve(&b);
+        for (x, x0) in x.iter().zip(x0.iter()) {
+            assert!((x - x0).abs() < 1e-12);
+        }
+
+        let mat2 = mat.map(|x| 2. * x);
+        ldlt.update(&mat2).unwrap();
+        for (x, x0) in ldlt.solve(&b).iter().zip(x0.iter()) {
+            assert!((2. * x - x0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn supernodal_large_solve() {
+        // large enough for the supernodes to hold dense blocks of
+        // several dozens of columns
+        let mat = laplacian_3d(10);
+        let n = mat.rows();
+        let b: Vec<f64> = (0..n).map(|i| (i % 11) as f64 - 5.).collect();
+        let ldlt = SupernodalLdlNumeric::new(&mat).unwrap();
+        let largest = (0..ldlt.nb_supernodes())
+            .map(|sn| ldlt.symbolic.sn_cols[sn + 1] - ldlt.symbolic.sn_cols[sn])
+            .max()
+            .unwrap();
+        assert!(largest >= 50);
+        let x = ldlt.solve(&b);
+        let res = (&mat * &x).unwrap();
+        for (r, b) in res.iter().zip(b.iter()) {
+            assert!((r - b).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn supernodal_empty() {
+        let mat = CsMatOwned::<f64>::new_owned(CSR, 0, 0, vec![0],
+                                               Vec::new(), Vec::new())
+                      .unwrap();
+        let ldlt = SupernodalLdlNumeric::new(&mat).unwrap();
+        assert_eq!(ldlt.nb_supernodes(), 0);
+        assert_eq!(ldlt.solve(&Vec::<f64>::new()), Vec::<f64>::new());
+    }
+
+    #[test]
+    fn supernodal_permuted_solve() {
+        // same system as cholesky::test::permuted_ldl_solve
+        let mat = CsMatOwned::new_owned(CSC,
+                                        4,
+                                        4,
+                                        vec![0, 2, 4, 6, 8],
+                                        vec![0, 3, 1, 2, 1, 2, 0, 3],
+                                        vec![1, 2, 21, 6, 6, 2, 2, 8])
+                      .unwrap();
+        let perm = Permutation::new(vec![0, 2, 1, 3]);
+        let symbolic = SupernodalLdlSymbolic::new_perm(&mat, perm).unwrap();
+        assert_eq!(symbolic.nnz(), 2);
+        let ldlt = symbolic.factor(&mat).unwrap();
+        let b = vec![9, 60, 18, 34];
+        assert_eq!(ldlt.solve(&b), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn supernodal_errors() {
+        let non_symmetric = CsMatOwned::new_owned(CSC, 2, 2,
+                                                  vec![0, 2, 3],
+                                                  vec![0, 1, 1],
+                                                  vec![1., 2., 1.]).unwrap();
+        assert_eq!(SupernodalLdlSymbolic::new(&non_symmetric).unwrap_err(),
+                   SprsError::NonSymmetricMatrix);
+
+        // |1 1 0|
+        // |1 1 0| has a zero pivot at index 1
+        // |0 0 1|
+        let singular = CsMatOwned::new_owned(CSC, 3, 3,
+                                             vec![0, 2, 4, 5],
+                                             vec![0, 1, 0, 1, 2],
+                                             vec![1., 1., 1., 1., 1.]).unwrap();
+        let ldlt = SupernodalLdlNumeric::new(&singular);
+        assert_eq!(ldlt.unwrap_err(), SprsError::SingularMatrix);
+        let regular = CsMatOwned::new_owned(CSC, 3, 3,
+                                            vec![0, 2, 4, 5],
+                                            vec![0, 1, 0, 1, 2],
+                                            vec![2., 1., 1., 2., 1.]).unwrap();
+        let mut ldlt = SupernodalLdlNumeric::new(&regular).unwrap();
+        assert_eq!(ldlt.update(&singular), Err(SprsError::SingularMatrix));
+        assert_eq!(ldlt.singular_pivot(), Some(1));
+        ldlt.update(&regular).unwrap();
+        assert_eq!(ldlt.singular_pivot(), None);
+        let mut ldlt = SupernodalLdlNumeric::new(&laplacian_3d(2)).unwrap();
+        assert_eq!(ldlt.update(&singular).unwrap_err(),
+                   SprsError::IncompatibleDimensions);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn supernodal_update_outside_pattern() {
+        let diag = CsMatOwned::new_owned(CSC, 2, 2, vec![0, 1, 2],
+                                         vec![0, 1], vec![1., 1.]).unwrap();
+        let full = CsMatOwned::new_owned(CSC, 2, 2, vec![0, 2, 4],
+                                         vec![0, 1, 0, 1],
+                                         vec![2., 1., 1., 2.]).unwrap();
+        let mut ldlt = SupernodalLdlNumeric::new(&diag).unwrap();
+        let _ = ldlt.update(&full);
+    }
+}
@@ -280,43 +280,147 @@ where N: Copy + Num
     // |      f       7| | w |     | c |     w = c / l7
 
     // compute the non-zero elements of the result by dfs traversal
-    for (root_ind, _) in rhs.iter() {
+    sparse_reach(&lower_tri_mat, rhs.indices(), Some, dstack, visited);
+
+    // solve for the non-zero values into dense workspace
+    for &ind in dstack.iter_right().map(stack::extract_stack_val) {
+        x_workspace[ind] = N::zero();
+    }
+    rhs.scatter(x_workspace);
+    for &ind in dstack.iter_right().map(stack::extract_stack_val) {
+        let col = lower_tri_mat.outer_view(ind).expect("ind not in bounds");
+        try!(lspsolve_csc_process_col(col, ind, x_workspace));
+    }
+
+    Ok(())
+}
+
+/// Sparse triangular CSC / sparse vector solve, where the rows of the
+/// lower triangular matrix are permuted
+///
+/// This is the solve performed by left looking factorizations, such as the
+/// LU factorization with partial pivoting, where the lower triangular factor
+/// is only partially known.
+///
+/// lower_tri_mat is a sparse matrix of shape (n, k), with k <= n
+/// row_to_col is of length n, and gives for each row the column of
+/// lower_tri_mat holding its diagonal element. Rows mapped to None have no
+/// diagonal element yet, and the corresponding unknowns only receive the
+/// updates from the other columns.
+/// The indices of the columns of lower_tri_mat need not be sorted.
+/// The other arguments are as in `lsolve_csc_sparse_rhs`, and the solve
+/// pattern is returned in the same way.
+///
+/// # Errors
+///
+/// * `BadStorageType` if lower_tri_mat is not CSC
+/// * `SingularMatrix` if a diagonal element is missing or zero
+///
+/// # Panics
+///
+/// * if dstack.capacity() is too small
+/// * if dstack is not empty
+/// * if x_workspace or row_to_col is not of length n
+pub fn lsolve_csc_sparse_rhs_perm<N>(lower_tri_mat: csmat::CsMatView<N>,
+                                     rhs: vec::CsVecView<N>,
+                                     row_to_col: &[Option<usize>],
+                                     dstack: &mut DStack<StackVal<usize>>,
+                                     x_workspace: &mut [N],
+                                     visited: &mut [bool])
+                                     -> Result<(), SprsError>
+where N: Copy + Num
+{
+    if !lower_tri_mat.is_csc() {
+        return Err(SprsError::BadStorageType);
+    }
+    let n = lower_tri_mat.rows();
+    assert!(dstack.capacity() >= 2 * n, "dstack cap should be 2*n");
+    assert!(dstack.is_left_empty() && dstack.is_right_empty(),
+            "dstack should be empty");
+    assert!(x_workspace.len() == n, "x should be of len n");
+    assert!(row_to_col.len() == n, "row_to_col should be of len n");
+
+    sparse_reach(&lower_tri_mat, rhs.indices(), |row| row_to_col[row],
+                 dstack, visited);
+
+    for &row in dstack.iter_right().map(stack::extract_stack_val) {
+        x_workspace[row] = N::zero();
+    }
+    rhs.scatter(x_workspace);
+    for &row in dstack.iter_right().map(stack::extract_stack_val) {
+        let col_ind = match row_to_col[row] {
+            Some(col_ind) => col_ind,
+            None => continue,
+        };
+        let col = lower_tri_mat.outer_view(col_ind)
+                               .expect("col_ind not in bounds");
+        let diag_val = match col.iter().find(|&(ind, _)| ind == row) {
+            Some((_, diag_val)) if diag_val != N::zero() => diag_val,
+            _ => return Err(SprsError::SingularMatrix),
+        };
+        let x = x_workspace[row] / diag_val;
+        x_workspace[row] = x;
+        for (row_ind, val) in col.iter() {
+            if row_ind != row {
+                let b = x_workspace[row_ind];
+                x_workspace[row_ind] = b - val * x;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Compute the rows reachable from roots in the graph of a CSC matrix,
+/// where the children of a row are the rows of the column given by
+/// outer_of. The reached rows are pushed on the right stack of dstack, in
+/// topological order.
+///
+/// The left stack holds the current dfs path, each node being stored as
+/// an `Enter` value followed by an `Exit` value giving the position of the
+/// next child to explore. As a node is either on the path or finished,
+/// a capacity of 2*n is enough.
+fn sparse_reach<N, F>(mat: &csmat::CsMatView<N>,
+                      roots: &[usize],
+                      outer_of: F,
+                      dstack: &mut DStack<StackVal<usize>>,
+                      visited: &mut [bool])
+where N: Copy,
+      F: Fn(usize) -> Option<usize>
+{
+    for &root_ind in roots {
         if visited[root_ind] {
             continue;
         }
+        visited[root_ind] = true;
         dstack.push_left(StackVal::Enter(root_ind));
-        while let Some(stack_val) = dstack.pop_left() {
-            match stack_val {
-                StackVal::Enter(ind) => {
-                    if visited[ind] {
-                        continue;
-                    }
-                    visited[ind] = true;
-                    dstack.push_left(StackVal::Exit(ind));
-                    if let Some(column) = lower_tri_mat.outer_view(ind) {
-                        for (child_ind, _) in column.iter() {
-                            dstack.push_left(StackVal::Enter(child_ind));
-                        }
-                    } else {
-                        unreachable!();
-                    }
+        dstack.push_left(StackVal::Exit(0));
+        while let Some(StackVal::Exit(child_pos)) = dstack.pop_left() {
+            let ind = match dstack.pop_left() {
+                Some(StackVal::Enter(ind)) => ind,
+                _ => unreachable!("dfs path is corrupted"),
+            };
+            let column = outer_of(ind).map(|outer_ind| {
+                mat.outer_view(outer_ind).expect("outer_ind not in bounds")
+            });
+            let children = column.as_ref().map_or(&[][..], |c| c.indices());
+            let next = children[child_pos..].iter()
+                                            .position(|&child| !visited[child]);
+            match next {
+                Some(offset) => {
+                    let child_ind = children[child_pos + offset];
+                    visited[child_ind] = true;
+                    dstack.push_left(StackVal::Enter(ind));
+                    dstack.push_left(StackVal::Exit(child_pos + offset + 1));
+                    dstack.push_left(StackVal::Enter(child_ind));
+                    dstack.push_left(StackVal::Exit(0));
                 }
-                StackVal::Exit(ind) => {
+                None => {
                     dstack.push_right(StackVal::Enter(ind));
                 }
             }
         }
     }
-
-    // solve for the non-zero values into dense workspace
-    rhs.scatter(x_workspace);
-    for &ind in dstack.iter_right().map(stack::extract_stack_val) {
-        println!("ind: {}", ind);
-        let col = lower_tri_mat.outer_view(ind).expect("ind not in bounds");
-        try!(lspsolve_csc_process_col(col, ind, x_workspace));
-    }
-
-    Ok(())
 }
 
 #[cfg(test)]
@@ -484,4 +588,126 @@ mod test {
 
         assert_eq!(x, expected_output);
     }
+
+    /// Dense lower triangular matrix where every row is reachable
+    /// from the first one, and whose last diagonal element is 1
+    fn dense_lower() -> Vec<Vec<f64>> {
+        vec![vec![2., 0., 0., 0., 0., 0.],
+             vec![1., 3., 0., 0., 0., 0.],
+             vec![0., -1., 4., 0., 0., 0.],
+             vec![2., 0., 1., 5., 0., 0.],
+             vec![0., 0., 0., -2., 1.5, 0.],
+             vec![1., 0., -3., 0., 1., 1.]]
+    }
+
+    /// CSC matrix holding the first cols columns of dense, with its row i
+    /// stored at row row_of[i]
+    fn csc_from_dense(dense: &[Vec<f64>],
+                      cols: usize,
+                      row_of: &[usize]) -> csmat::CsMatOwned<f64> {
+        let n = dense.len();
+        let mut indptr = vec![0];
+        let mut indices = Vec::new();
+        let mut data = Vec::new();
+        for j in 0..cols {
+            let mut col: Vec<_> = dense.iter()
+                                       .zip(row_of)
+                                       .filter(|&(row, _)| row[j] != 0.)
+                                       .map(|(row, &stored)| (stored, row[j]))
+                                       .collect();
+            col.sort_by_key(|&(row, _)| row);
+            indices.extend(col.iter().map(|&(row, _)| row));
+            data.extend(col.iter().map(|&(_, val)| val));
+            indptr.push(indices.len());
+        }
+        csmat::CsMatOwned::new_owned(csmat::CompressedStorage::CSC, n, cols,
+                                     indptr, indices, data).unwrap()
+    }
+
+    #[test]
+    fn lspsolve_csc_against_dense() {
+        let dense = dense_lower();
+        let n = dense.len();
+        let identity: Vec<_> = (0..n).collect();
+        let l = csc_from_dense(&dense, n, &identity);
+        // the first rhs reaches every row, the second only the last two
+        let rhs = [vec::CsVecOwned::new_owned(n, vec![0], vec![2.]).unwrap(),
+                   vec::CsVecOwned::new_owned(n, vec![0, 3],
+                                              vec![1., -2.]).unwrap(),
+                   vec::CsVecOwned::new_owned(n, vec![4], vec![3.]).unwrap()];
+        let expected_reach = [n, n, 2];
+        for (b, &reach) in rhs.iter().zip(&expected_reach) {
+            let mut expected = vec![0.; n];
+            b.scatter(&mut expected);
+            super::lsolve_csc_dense_rhs(l.borrowed(), &mut expected).unwrap();
+
+            let mut dstack = DStack::with_capacity(2 * n);
+            let mut xw = vec![7.; n];
+            let mut visited = vec![false; n];
+            super::lsolve_csc_sparse_rhs(l.borrowed(), b.borrowed(),
+                                         &mut dstack, &mut xw, &mut visited)
+                .unwrap();
+            let reached: Vec<_> = dstack.iter_right()
+                                        .map(stack::extract_stack_val)
+                                        .cloned()
+                                        .collect();
+            assert_eq!(reached.len(), reach);
+            for i in 0..n {
+                if reached.contains(&i) {
+                    assert!((xw[i] - expected[i]).abs() < 1e-12);
+                }
+                else {
+                    assert_eq!(expected[i], 0.);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lspsolve_csc_perm_against_dense() {
+        let dense = dense_lower();
+        let n = dense.len();
+        let row_of = [3, 0, 5, 1, 4, 2];
+        let b_dense = [2., 0., 0., 1., 0., -1.];
+        let mut expected = b_dense.to_vec();
+        let l = csc_from_dense(&dense, n, &(0..n).collect::<Vec<_>>());
+        super::lsolve_csc_dense_rhs(l.borrowed(), &mut expected).unwrap();
+
+        // the rhs in the storage order of the permuted rows
+        let mut b_entries: Vec<_> = (0..n).filter(|&i| b_dense[i] != 0.)
+                                          .map(|i| (row_of[i], b_dense[i]))
+                                          .collect();
+        b_entries.sort_by_key(|&(row, _)| row);
+        let b = vec::CsVecOwned::new_owned(
+            n,
+            b_entries.iter().map(|&(row, _)| row).collect(),
+            b_entries.iter().map(|&(_, val)| val).collect()).unwrap();
+
+        // with all the columns, then without the last one, whose unknown
+        // then only receives the updates, as its diagonal element is 1
+        for &cols in &[n, n - 1] {
+            let permuted = csc_from_dense(&dense, cols, &row_of);
+            let mut row_to_col = vec![None; n];
+            for j in 0..cols {
+                row_to_col[row_of[j]] = Some(j);
+            }
+            let mut dstack = DStack::with_capacity(2 * n);
+            let mut xw = vec![7.; n];
+            let mut visited = vec![false; n];
+            super::lsolve_csc_sparse_rhs_perm(permuted.borrowed(),
+                                              b.borrowed(),
+                                              &row_to_col,
+                                              &mut dstack,
+                                              &mut xw,
+                                              &mut visited).unwrap();
+            let reached: HashSet<_> = dstack.iter_right()
+                                            .map(stack::extract_stack_val)
+                                            .cloned()
+                                            .collect();
+            assert_eq!(reached.len(), n);
+            for i in 0..n {
+                assert!((xw[row_of[i]] - expected[i]).abs() < 1e-12);
+            }
+        }
+    }
 }
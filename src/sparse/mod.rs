@@ -10,6 +10,8 @@ pub use self::vec::{CsVec,
                     CsVecView,
 };
 
+pub use self::accumulator::SparseAccumulator;
+
 
 pub mod csmat;
 pub mod triplet;
@@ -21,4 +23,6 @@ pub mod construct;
 pub mod linalg;
 pub mod symmetric;
 pub mod compressed;
+pub mod semiring;
+pub mod accumulator;
 
@@ -1,12 +1,21 @@
 ///! Sparse matrix product
 
-use sparse::csmat::{CsMatOwned, CsMatView};
+use sparse::csmat::{CsMat, CsMatOwned, CsMatView};
+use sparse::csmat::CompressedStorage::{self, CSR, CSC};
 use sparse::vec::{CsVecView, CsVecOwned};
 use num::traits::Num;
 use sparse::compressed::SpMatView;
+use sparse::semiring::{Semiring, PlusTimes};
+use sparse::accumulator::SparseAccumulator;
+use sparse::vec::SparseIterTools;
 use dense_mats::{StorageOrder, MatView, MatViewMut};
 use dense_mats::tensor;
 use errors::SprsError;
+use utils;
+use std::cmp;
+use std::ops::{Deref, Range};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
 /// Multiply a sparse CSC matrix with a dense vector and accumulate the result
 /// into another dense vector
@@ -65,17 +74,20 @@ where N: Num + Copy {
 ///
 /// lhs: left hand size matrix
 /// rhs: right hand size matrix
-/// workspace: used to accumulate the line values. Should be of length
-///            rhs.cols()
+/// workspace: ignored, the lines are accumulated into a sparse accumulator
+///            allocated by this function
+#[deprecated(note = "the workspace is ignored, use csr_mul_csr_spa")]
 pub fn csr_mul_csr<N, Mat1, Mat2>(lhs: &Mat1,
                                   rhs: &Mat2,
-                                  workspace: &mut[N]
+                                  _workspace: &mut[N]
                                  ) -> Result<CsMatOwned<N>, SprsError>
 where
 N: Num + Copy,
 Mat1: SpMatView<N>,
 Mat2: SpMatView<N> {
-    csr_mul_csr_impl(lhs.borrowed(), rhs.borrowed(), workspace)
+    let mut spa = SparseAccumulator::new_filled(rhs.borrowed().cols(),
+                                                N::zero());
+    csr_mul_csr_spa(lhs, rhs, &mut spa)
 }
 
 /// Perform a matrix multiplication for matrices sharing the same storage order.
@@ -85,22 +97,86 @@ Mat2: SpMatView<N> {
 ///
 /// lhs: left hand size matrix
 /// rhs: right hand size matrix
-/// workspace: used to accumulate the line values. Should be of length
-///            lhs.lines()
+/// workspace: ignored, the lines are accumulated into a sparse accumulator
+///            allocated by this function
+#[deprecated(note = "the workspace is ignored, use csc_mul_csc_spa")]
 pub fn csc_mul_csc<N, Mat1, Mat2>(lhs: &Mat1,
                                   rhs: &Mat2,
-                                  workspace: &mut[N]
+                                  _workspace: &mut[N]
                                  ) -> Result<CsMatOwned<N>, SprsError>
 where
 N: Num + Copy,
 Mat1: SpMatView<N>,
 Mat2: SpMatView<N> {
-    csr_mul_csr_impl(rhs.transpose_view(),
-                     lhs.transpose_view(),
-                     workspace).map(|x| x.transpose_into())
+    let mut spa = SparseAccumulator::new_filled(lhs.borrowed().rows(),
+                                                N::zero());
+    csc_mul_csc_spa(lhs, rhs, &mut spa)
+}
+
+/// Perform a matrix multiplication for CSR matrices, accumulating each
+/// line of the result into a sparse accumulator.
+///
+/// The cost of this product is proportional to the number of flops it
+/// performs. Numerical zeros are not stored in the result.
+///
+/// lhs: left hand size matrix
+/// rhs: right hand size matrix
+/// spa: used to accumulate the line values. Should be of dimension
+///      rhs.cols(). It is reset before use, and can be reused across
+///      products to avoid allocating it each time.
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CSR, SparseAccumulator};
+/// use sprs::sparse::prod;
+/// let a = CsMat::new_owned(CSR, 2, 2, vec![0, 2, 3], vec![0, 1, 1],
+///                          vec![1., 2., 3.]).unwrap();
+/// let mut spa = SparseAccumulator::new(a.cols());
+/// let a2 = prod::csr_mul_csr_spa(&a, &a, &mut spa).unwrap();
+/// let a3 = prod::csr_mul_csr_spa(&a2, &a, &mut spa).unwrap();
+/// assert_eq!(a3, &(&a * &a) * &a);
+/// ```
+pub fn csr_mul_csr_spa<N, Mat1, Mat2>(lhs: &Mat1,
+                                      rhs: &Mat2,
+                                      spa: &mut SparseAccumulator<N>
+                                     ) -> Result<CsMatOwned<N>, SprsError>
+where
+N: Num + Copy,
+Mat1: SpMatView<N>,
+Mat2: SpMatView<N> {
+    let lhs = lhs.borrowed();
+    let rhs = rhs.borrowed();
+    let mut indptr = Vec::with_capacity(lhs.rows() + 1);
+    let mut indices = Vec::with_capacity(lhs.nb_nonzero() + rhs.nb_nonzero());
+    let mut data = Vec::with_capacity(lhs.nb_nonzero() + rhs.nb_nonzero());
+    csr_mul_csr_raw(lhs.borrowed(), rhs.borrowed(), IndexOrdering::Sorted,
+                    spa, &mut indptr, &mut indices, &mut data)?;
+    CsMatOwned::new_owned(lhs.storage(), lhs.rows(), rhs.cols(),
+                          indptr, indices, data)
+}
+
+/// Perform a matrix multiplication for CSC matrices, accumulating each
+/// line of the result into a sparse accumulator.
+///
+/// This uses free transposition to invoke `csr_mul_csr_spa`, so spa should
+/// be of dimension lhs.rows().
+pub fn csc_mul_csc_spa<N, Mat1, Mat2>(lhs: &Mat1,
+                                      rhs: &Mat2,
+                                      spa: &mut SparseAccumulator<N>
+                                     ) -> Result<CsMatOwned<N>, SprsError>
+where
+N: Num + Copy,
+Mat1: SpMatView<N>,
+Mat2: SpMatView<N> {
+    csr_mul_csr_spa(&rhs.transpose_view(),
+                    &lhs.transpose_view(),
+                    spa).map(|x| x.transpose_into())
 }
 
 /// Allocate the appropriate workspace for a CSR-CSR product
+#[deprecated(note = "the products do not use this workspace anymore, \
+                     see csr_mul_csr_spa")]
 pub fn workspace_csr<N, Mat1, Mat2>(_: &Mat1, rhs: &Mat2) -> Vec<N>
 where N: Copy + Num,
       Mat1: SpMatView<N>,
@@ -110,6 +186,8 @@ where N: Copy + Num,
 }
 
 /// Allocate the appropriate workspace for a CSC-CSC product
+#[deprecated(note = "the products do not use this workspace anymore, \
+                     see csc_mul_csc_spa")]
 pub fn workspace_csc<N, Mat1, Mat2>(lhs: &Mat1, _: &Mat2) -> Vec<N>
 where N: Copy + Num,
       Mat1: SpMatView<N>,
@@ -119,52 +197,1145 @@ where N: Copy + Num,
 }
 
 /// Actual implementation of CSR-CSR multiplication
-/// All other matrix products are implemented in terms of this one.
+///
+/// workspace: ignored, the lines are accumulated into a sparse accumulator
+///            allocated by this function
+#[deprecated(note = "the workspace is ignored, use csr_mul_csr_spa")]
 pub fn csr_mul_csr_impl<N>(lhs: CsMatView<N>,
                            rhs: CsMatView<N>,
-                           workspace: &mut[N]
+                           _workspace: &mut[N]
                           ) -> Result<CsMatOwned<N>, SprsError>
 where N: Num + Copy {
-    let res_rows = lhs.rows();
-    let res_cols = rhs.cols();
+    let mut spa = SparseAccumulator::new_filled(rhs.cols(), N::zero());
+    csr_mul_csr_spa(&lhs, &rhs, &mut spa)
+}
+
+/// Ordering of the inner indices of each outer dimension in the output
+/// of a raw product
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IndexOrdering {
+    /// Indices are sorted, as required by CsMat
+    Sorted,
+    /// Indices are in their order of discovery, which saves sorting them
+    /// when the consumer of the product does not need sorted indices
+    Unsorted,
+}
+
+/// Raw CSR-CSR multiplication using a sparse accumulator, writing the
+/// resulting arrays into the provided vectors.
+///
+/// The output vectors are cleared before the result is written. The cost
+/// of this product is proportional to the number of flops it performs,
+/// which makes it suitable for matrices with a very large number of columns.
+/// Numerical zeros are not stored in the result.
+///
+/// The CSC-CSC product of lhs and rhs can be obtained as the CSR arrays of
+/// the product of rhs.transpose_view() and lhs.transpose_view().
+///
+/// spa: used to accumulate the line values. Should be of dimension
+///      rhs.cols(). It is reset before use, and can be reused across
+///      products to avoid allocating it each time.
+pub fn csr_mul_csr_raw<N>(lhs: CsMatView<N>,
+                          rhs: CsMatView<N>,
+                          ordering: IndexOrdering,
+                          spa: &mut SparseAccumulator<N>,
+                          out_indptr: &mut Vec<usize>,
+                          out_indices: &mut Vec<usize>,
+                          out_data: &mut Vec<N>
+                         ) -> Result<(), SprsError>
+where N: Num + Copy {
+    check_csr_mul_csr(&lhs, &rhs)?;
+    if rhs.cols() != spa.dim() {
+        return Err(SprsError::BadWorkspaceDimensions);
+    }
+
+    out_indptr.clear();
+    out_indices.clear();
+    out_data.clear();
+    out_indptr.push(0);
+    spa.reset();
+    for (_, lvec) in lhs.outer_iterator() {
+        accumulate_row_product(&PlusTimes, lvec, &rhs, spa);
+        spa.gather_filtered_into(ordering, |val| val != N::zero(),
+                                 out_indices, out_data);
+        spa.reset();
+        out_indptr.push(out_indices.len());
+    }
+    Ok(())
+}
+
+/// Accumulate the product of the row lvec of lhs with rhs into spa
+fn accumulate_row_product<N, S>(semiring: &S,
+                                lvec: CsVecView<N>,
+                                rhs: &CsMatView<N>,
+                                spa: &mut SparseAccumulator<N>)
+where N: Copy,
+      S: Semiring<N> {
+    for (lcol, lval) in lvec.iter() {
+        // we can't be out of bounds thanks to the checks of dimension
+        // compatibility and the structure check of CsMat
+        let rvec = rhs.outer_view(lcol).unwrap();
+        for (rcol, rval) in rvec.iter() {
+            spa.accumulate_with(rcol, semiring.mul(lval, rval),
+                                |acc, prod| semiring.add(acc, prod));
+        }
+    }
+}
+
+/// Compute the nonzero pattern of the product of two CSR matrices.
+///
+/// The returned matrix has the exact structural pattern of the product
+/// (no cancellation is taken into account), with sorted indices and
+/// zero values. Its values can then be computed by `csr_mul_csr_numeric`,
+/// which can be called again whenever the values of lhs or rhs change while
+/// their patterns stay the same.
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CSR, SparseAccumulator};
+/// use sprs::sparse::prod;
+/// let a = CsMat::new_owned(CSR, 2, 2, vec![0, 2, 3], vec![0, 1, 1],
+///                          vec![1., 2., 3.]).unwrap();
+/// let mut c = prod::csr_mul_csr_symbolic(&a, &a).unwrap();
+/// let mut spa = SparseAccumulator::new(a.cols());
+/// prod::csr_mul_csr_numeric(&a, &a, &mut c, &mut spa).unwrap();
+/// assert_eq!(c, &a * &a);
+/// ```
+pub fn csr_mul_csr_symbolic<N, Mat1, Mat2>(lhs: &Mat1,
+                                           rhs: &Mat2
+                                          ) -> Result<CsMatOwned<N>, SprsError>
+where
+N: Num + Copy,
+Mat1: SpMatView<N>,
+Mat2: SpMatView<N> {
+    csr_mul_csr_symbolic_impl(lhs.borrowed(), rhs.borrowed())
+}
+
+/// Compute the nonzero pattern of the product of two CSC matrices.
+///
+/// See `csr_mul_csr_symbolic` for details.
+pub fn csc_mul_csc_symbolic<N, Mat1, Mat2>(lhs: &Mat1,
+                                           rhs: &Mat2
+                                          ) -> Result<CsMatOwned<N>, SprsError>
+where
+N: Num + Copy,
+Mat1: SpMatView<N>,
+Mat2: SpMatView<N> {
+    csr_mul_csr_symbolic_impl(rhs.transpose_view(),
+                              lhs.transpose_view()).map(|x| x.transpose_into())
+}
+
+/// Compute the values of the product of two CSR matrices into res,
+/// whose pattern should have been computed by `csr_mul_csr_symbolic`.
+///
+/// spa: used to accumulate the line values. Should be of dimension
+///      rhs.cols(). It is reset before use, so keeping it across numeric
+///      refreshes avoids any allocation.
+///
+/// # Errors
+///
+/// * `IncompatiblePatterns` if the product has a nonzero outside
+///   the pattern of res. The values of res are then unspecified.
+pub fn csr_mul_csr_numeric<N, Mat1, Mat2>(lhs: &Mat1,
+                                          rhs: &Mat2,
+                                          res: &mut CsMatOwned<N>,
+                                          spa: &mut SparseAccumulator<N>
+                                         ) -> Result<(), SprsError>
+where
+N: Num + Copy,
+Mat1: SpMatView<N>,
+Mat2: SpMatView<N> {
+    if !res.is_csr() {
+        return Err(SprsError::BadStorageType);
+    }
+    csr_mul_csr_numeric_impl(lhs.borrowed(), rhs.borrowed(), res, spa)
+}
+
+/// Compute the values of the product of two CSC matrices into res,
+/// whose pattern should have been computed by `csc_mul_csc_symbolic`.
+///
+/// spa: used to accumulate the column values. Should be of dimension
+///      lhs.rows()
+///
+/// See `csr_mul_csr_numeric` for details.
+pub fn csc_mul_csc_numeric<N, Mat1, Mat2>(lhs: &Mat1,
+                                          rhs: &Mat2,
+                                          res: &mut CsMatOwned<N>,
+                                          spa: &mut SparseAccumulator<N>
+                                         ) -> Result<(), SprsError>
+where
+N: Num + Copy,
+Mat1: SpMatView<N>,
+Mat2: SpMatView<N> {
+    if !res.is_csc() {
+        return Err(SprsError::BadStorageType);
+    }
+    // the arrays of res are those of its CSR transpose
+    res.transpose_mut();
+    let status = csr_mul_csr_numeric_impl(rhs.transpose_view(),
+                                          lhs.transpose_view(),
+                                          res,
+                                          spa);
+    res.transpose_mut();
+    status
+}
+
+fn check_csr_mul_csr<N>(lhs: &CsMatView<N>,
+                        rhs: &CsMatView<N>) -> Result<(), SprsError>
+where N: Copy {
     if lhs.cols() != rhs.rows() {
         return Err(SprsError::IncompatibleDimensions);
     }
-    if res_cols !=  workspace.len() {
+    if lhs.storage() != rhs.storage() {
+        return Err(SprsError::IncompatibleStorages);
+    }
+    if !rhs.is_csr() {
+        return Err(SprsError::BadStorageType);
+    }
+    Ok(())
+}
+
+/// Actual implementation of the CSR-CSR symbolic product
+pub fn csr_mul_csr_symbolic_impl<N>(lhs: CsMatView<N>,
+                                    rhs: CsMatView<N>
+                                   ) -> Result<CsMatOwned<N>, SprsError>
+where N: Num + Copy {
+    check_csr_mul_csr(&lhs, &rhs)?;
+    let res_cols = rhs.cols();
+
+    // marker[col] == row means col is already in the pattern of row
+    let mut marker = vec![usize::MAX; res_cols];
+    let mut indptr = Vec::with_capacity(lhs.rows() + 1);
+    let mut indices = Vec::with_capacity(lhs.nb_nonzero() + rhs.nb_nonzero());
+    indptr.push(0);
+    for (row, lvec) in lhs.outer_iterator() {
+        let row_start = indices.len();
+        for &lcol in lvec.indices() {
+            let rvec = rhs.outer_view(lcol).unwrap();
+            for &rcol in rvec.indices() {
+                if marker[rcol] != row {
+                    marker[rcol] = row;
+                    indices.push(rcol);
+                }
+            }
+        }
+        indices[row_start..].sort_unstable();
+        indptr.push(indices.len());
+    }
+    let data = vec![N::zero(); indices.len()];
+    CsMatOwned::new_owned(lhs.storage(), lhs.rows(), res_cols,
+                          indptr, indices, data)
+}
+
+/// Actual implementation of the CSR-CSR numeric product. The storage of
+/// res is not checked, its arrays are interpreted as CSR.
+fn csr_mul_csr_numeric_impl<N>(lhs: CsMatView<N>,
+                               rhs: CsMatView<N>,
+                               res: &mut CsMatOwned<N>,
+                               spa: &mut SparseAccumulator<N>
+                              ) -> Result<(), SprsError>
+where N: Num + Copy {
+    check_csr_mul_csr(&lhs, &rhs)?;
+    if res.rows() != lhs.rows() || res.cols() != rhs.cols() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if rhs.cols() != spa.dim() {
+        return Err(SprsError::BadWorkspaceDimensions);
+    }
+
+    spa.reset();
+    let (indptr, indices, data) = res.structure_data_mut();
+    for (row, lvec) in lhs.outer_iterator() {
+        let row_range = indptr[row]..indptr[row + 1];
+        // the pattern of the row in res is the pattern of the accumulator
+        for &col in &indices[row_range.clone()] {
+            spa.scatter(col, N::zero());
+        }
+        // accumulate the resulting row
+        for (lcol, lval) in lvec.iter() {
+            let rvec = rhs.outer_view(lcol).unwrap();
+            for (rcol, rval) in rvec.iter() {
+                if spa.get(rcol).is_none() {
+                    return Err(SprsError::IncompatiblePatterns);
+                }
+                spa.accumulate(rcol, lval * rval);
+            }
+        }
+        for (&col, val) in indices[row_range.clone()].iter()
+                                                     .zip(&mut data[row_range]) {
+            *val = spa.get(col).unwrap();
+        }
+        spa.reset();
+    }
+    Ok(())
+}
+
+/// Compute the nonzero pattern of the triple product r * a * p, as used
+/// to build the coarse operators of multigrid methods.
+///
+/// a and p should be CSR matrices, while r can have any storage. The
+/// returned matrix is in CSR storage, has the structural pattern of the
+/// product with sorted indices and zero values. Its values can then be
+/// computed by `rap_numeric`, which can be called again whenever the values
+/// of the operands change while their patterns stay the same.
+///
+/// The intermediate product a * p is never materialized.
+pub fn rap_symbolic<N, MatR, MatA, MatP>(r: &MatR,
+                                         a: &MatA,
+                                         p: &MatP
+                                        ) -> Result<CsMatOwned<N>, SprsError>
+where N: Num + Copy,
+      MatR: SpMatView<N>,
+      MatA: SpMatView<N>,
+      MatP: SpMatView<N> {
+    let r = r.borrowed();
+    let a = a.borrowed();
+    let p = p.borrowed();
+    check_rap(&r, &a, &p)?;
+    let r_index = if r.is_csc() { Some(other_storage_index(&r)) } else { None };
+    let r_rows = match r_index {
+        Some(ref index) => OuterAccess::indexed(index, r.data()),
+        None => OuterAccess::direct(&r),
+    };
+
+    // marker[col] == row means col is already in the pattern of row
+    let mut marker = vec![usize::MAX; p.cols()];
+    let mut indptr = Vec::with_capacity(r.rows() + 1);
+    let mut indices = Vec::new();
+    indptr.push(0);
+    for row in 0..r.rows() {
+        let row_start = indices.len();
+        r_rows.for_each_nnz(row, |k, _| {
+            for &l in a.outer_view(k).unwrap().indices() {
+                for &col in p.outer_view(l).unwrap().indices() {
+                    if marker[col] != row {
+                        marker[col] = row;
+                        indices.push(col);
+                    }
+                }
+            }
+        });
+        indices[row_start..].sort_unstable();
+        indptr.push(indices.len());
+    }
+    let data = vec![N::zero(); indices.len()];
+    CsMatOwned::new_owned(CSR, r.rows(), p.cols(), indptr, indices, data)
+}
+
+/// Compute the values of the triple product r * a * p into res, whose
+/// pattern should have been computed by `rap_symbolic`.
+///
+/// When r is a CSC matrix, typically the transpose view of a CSR p, each
+/// row of a * p is computed only once and scattered into the result.
+/// When r is a CSR matrix, each row of the result is accumulated in turn.
+///
+/// spa: used to accumulate the line values. Should be of dimension
+///      p.cols(). It is reset before use.
+///
+/// # Errors
+///
+/// `IncompatiblePatterns` is returned if the product has nonzeros outside
+/// the pattern of res, and `BadStorageType` if res is not a CSR matrix.
+pub fn rap_numeric<N, MatR, MatA, MatP>(r: &MatR,
+                                        a: &MatA,
+                                        p: &MatP,
+                                        res: &mut CsMatOwned<N>,
+                                        spa: &mut SparseAccumulator<N>
+                                       ) -> Result<(), SprsError>
+where N: Num + Copy,
+      MatR: SpMatView<N>,
+      MatA: SpMatView<N>,
+      MatP: SpMatView<N> {
+    let r = r.borrowed();
+    let a = a.borrowed();
+    let p = p.borrowed();
+    check_rap(&r, &a, &p)?;
+    if !res.is_csr() {
+        return Err(SprsError::BadStorageType);
+    }
+    if res.rows() != r.rows() || res.cols() != p.cols() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if p.cols() != spa.dim() {
         return Err(SprsError::BadWorkspaceDimensions);
     }
-    if lhs.storage() != rhs.storage() {
-        return Err(SprsError::IncompatibleStorages);
+    spa.reset();
+    match r.storage() {
+        CSR => rap_numeric_rows(r, a, p, res, spa),
+        CSC => rap_numeric_outer(r, a, p, res, spa),
+    }
+}
+
+/// Compute the triple product r * a * p
+///
+/// See `rap_symbolic` and `rap_numeric`.
+pub fn rap<N, MatR, MatA, MatP>(r: &MatR,
+                                a: &MatA,
+                                p: &MatP
+                               ) -> Result<CsMatOwned<N>, SprsError>
+where N: Num + Copy,
+      MatR: SpMatView<N>,
+      MatA: SpMatView<N>,
+      MatP: SpMatView<N> {
+    let mut res = rap_symbolic(r, a, p)?;
+    let mut spa = SparseAccumulator::new_filled(res.cols(), N::zero());
+    rap_numeric(r, a, p, &mut res, &mut spa)?;
+    Ok(res)
+}
+
+/// Compute the Galerkin product p^T * a * p of CSR matrices, without
+/// converting the storage of p.
+///
+/// When the product has to be refreshed for new values of a, the symbolic
+/// and numeric phases can be separated by calling `rap_symbolic` and
+/// `rap_numeric` with `p.transpose_view()` as r.
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CSR, SparseAccumulator};
+/// use sprs::sparse::prod;
+/// // 1D Laplacian, aggregated by pairs of nodes
+/// let a = CsMat::new_owned(CSR, 4, 4, vec![0, 2, 5, 8, 10],
+///                          vec![0, 1, 0, 1, 2, 1, 2, 3, 2, 3],
+///                          vec![2., -1., -1., 2., -1., -1., 2., -1., -1., 2.])
+///                          .unwrap();
+/// let p = CsMat::new_owned(CSR, 4, 2, vec![0, 1, 2, 3, 4],
+///                          vec![0, 0, 1, 1], vec![1.; 4]).unwrap();
+/// let coarse = prod::galerkin_product(&a, &p).unwrap();
+/// assert_eq!(coarse.data(), &[2., -1., -1., 2.]);
+///
+/// let mut coarse = prod::rap_symbolic(&p.transpose_view(), &a, &p).unwrap();
+/// let mut spa = SparseAccumulator::new(p.cols());
+/// let a = &a * 2.;
+/// prod::rap_numeric(&p.transpose_view(), &a, &p, &mut coarse,
+///                   &mut spa).unwrap();
+/// assert_eq!(coarse.data(), &[4., -2., -2., 4.]);
+/// ```
+pub fn galerkin_product<N, MatA, MatP>(a: &MatA,
+                                       p: &MatP
+                                      ) -> Result<CsMatOwned<N>, SprsError>
+where N: Num + Copy,
+      MatA: SpMatView<N>,
+      MatP: SpMatView<N> {
+    rap(&p.transpose_view(), a, p)
+}
+
+fn check_rap<N>(r: &CsMatView<N>,
+                a: &CsMatView<N>,
+                p: &CsMatView<N>) -> Result<(), SprsError>
+where N: Copy {
+    if r.cols() != a.rows() || a.cols() != p.rows() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if !a.is_csr() || !p.is_csr() {
+        return Err(SprsError::BadStorageType);
+    }
+    Ok(())
+}
+
+/// Numeric triple product accumulating the rows of the result in turn
+fn rap_numeric_rows<N>(r: CsMatView<N>,
+                       a: CsMatView<N>,
+                       p: CsMatView<N>,
+                       res: &mut CsMatOwned<N>,
+                       spa: &mut SparseAccumulator<N>
+                      ) -> Result<(), SprsError>
+where N: Num + Copy {
+    let (indptr, indices, data) = res.structure_data_mut();
+    for (row, rvec) in r.outer_iterator() {
+        let row_range = indptr[row]..indptr[row + 1];
+        // the pattern of the row in res is the pattern of the accumulator
+        for &col in &indices[row_range.clone()] {
+            spa.scatter(col, N::zero());
+        }
+        for (k, rval) in rvec.iter() {
+            for (l, aval) in a.outer_view(k).unwrap().iter() {
+                let raval = rval * aval;
+                for (col, pval) in p.outer_view(l).unwrap().iter() {
+                    if spa.get(col).is_none() {
+                        return Err(SprsError::IncompatiblePatterns);
+                    }
+                    spa.accumulate(col, raval * pval);
+                }
+            }
+        }
+        let row_indices = &indices[row_range.clone()];
+        for (&col, val) in row_indices.iter().zip(&mut data[row_range]) {
+            *val = spa.get(col).unwrap();
+        }
+        spa.reset();
+    }
+    Ok(())
+}
+
+/// Numeric triple product computing each row of a * p once and scattering
+/// it into the rows of the result given by the corresponding column of r,
+/// which should be a CSC matrix.
+fn rap_numeric_outer<N>(r: CsMatView<N>,
+                        a: CsMatView<N>,
+                        p: CsMatView<N>,
+                        res: &mut CsMatOwned<N>,
+                        spa: &mut SparseAccumulator<N>
+                       ) -> Result<(), SprsError>
+where N: Num + Copy {
+    let (indptr, indices, data) = res.structure_data_mut();
+    for val in data.iter_mut() {
+        *val = N::zero();
+    }
+    for ((_, avec), (_, rvec)) in a.outer_iterator().zip(r.outer_iterator()) {
+        if rvec.nnz() == 0 {
+            continue;
+        }
+        accumulate_row_product(&PlusTimes, avec, &p, spa);
+        for (row, rval) in rvec.iter() {
+            let row_start = indptr[row];
+            let row_indices = &indices[row_start..indptr[row + 1]];
+            for &col in spa.indices() {
+                let pos = row_indices.binary_search(&col).map_err(|_| {
+                    SprsError::IncompatiblePatterns
+                })?;
+                let val = &mut data[row_start + pos];
+                *val = *val + rval * spa.get(col).unwrap();
+            }
+        }
+        spa.reset();
+    }
+    Ok(())
+}
+
+/// Operation applied to a sparse matrix before a product
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Transpose {
+    /// Use the matrix as is
+    No,
+    /// Use the transpose of the matrix
+    Yes,
+}
+
+fn apply_transpose<'a, N: Copy>(a: CsMatView<'a, N>, trans: Transpose)
+                          -> CsMatView<'a, N> {
+    match trans {
+        Transpose::No => a,
+        Transpose::Yes => a.transpose_into(),
+    }
+}
+
+/// Compute y = alpha * op(a) * x + beta * y, where op(a) is a or its
+/// transpose depending on trans.
+///
+/// Any storage of a is handled without conversion. As in BLAS, when beta
+/// is zero the prior content of y is not read, so it can be uninitialized
+/// or contain NaNs.
+///
+/// # Errors
+///
+/// - `IncompatibleDimensions` if the dimensions of op(a), x and y do not
+///   match
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CSC};
+/// use sprs::sparse::prod::{self, Transpose};
+/// let a = CsMat::new_owned(CSC, 2, 2, vec![0, 1, 2], vec![0, 1],
+///                          vec![1., 2.]).unwrap();
+/// let mut y = vec![1., 1.];
+/// prod::spmv(2., &a, Transpose::No, &[1., 1.], -1., &mut y).unwrap();
+/// assert_eq!(y, vec![1., 3.]);
+/// ```
+pub fn spmv<N, Mat>(alpha: N,
+                    a: &Mat,
+                    trans: Transpose,
+                    x: &[N],
+                    beta: N,
+                    y: &mut [N]) -> Result<(), SprsError>
+where N: Num + Copy,
+      Mat: SpMatView<N> {
+    let a = apply_transpose(a.borrowed(), trans);
+    if a.cols() != x.len() || a.rows() != y.len() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    for val in y.iter_mut() {
+        *val = if beta == N::zero() { N::zero() } else { beta * *val };
+    }
+    match a.storage() {
+        CSR => {
+            for (row, vec) in a.outer_iterator() {
+                let sum = vec.iter().fold(N::zero(), |acc, (col, val)| {
+                    acc + val * x[col]
+                });
+                y[row] = y[row] + alpha * sum;
+            }
+        }
+        CSC => {
+            for (col, vec) in a.outer_iterator() {
+                let x_col = alpha * x[col];
+                for (row, val) in vec.iter() {
+                    y[row] = y[row] + val * x_col;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Compute c = alpha * op(a) * b + beta * c, where op(a) is a or its
+/// transpose depending on trans.
+///
+/// Any storage of a and any ordering of b and c are handled. As in BLAS,
+/// when beta is zero the prior content of c is not read.
+///
+/// # Errors
+///
+/// - `IncompatibleDimensions` if the dimensions of op(a), b and c do not
+///   match
+pub fn spmm<'a, N, Mat>(alpha: N,
+                        a: &Mat,
+                        trans: Transpose,
+                        b: MatView<N>,
+                        beta: N,
+                        mut c: MatViewMut<'a, N>) -> Result<(), SprsError>
+where N: 'a + Num + Copy,
+      Mat: SpMatView<N> {
+    let a = apply_transpose(a.borrowed(), trans);
+    if a.cols() != b.rows() || a.rows() != c.rows() || b.cols() != c.cols() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    let axis0 = tensor::Axis(0);
+    for mut line in c.iter_axis_mut(axis0) {
+        for val in line.iter_mut() {
+            *val = if beta == N::zero() { N::zero() } else { beta * *val };
+        }
+    }
+    for (outer, vec) in a.outer_iterator() {
+        for (inner, val) in vec.iter() {
+            let (row, k) = match a.storage() {
+                CSR => (outer, inner),
+                CSC => (inner, outer),
+            };
+            let factor = alpha * val;
+            let bline = b.slice_dim(axis0, k);
+            let mut cline = c.slice_dim_mut(axis0, row);
+            for (cval, &bval) in cline.iter_mut().zip(bline.iter()) {
+                *cval = *cval + factor * bval;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Part of a symmetric matrix to compute
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Triangle {
+    /// Only the upper triangle, including the diagonal
+    Upper,
+    /// The full matrix
+    Full,
+}
+
+/// Compute the Gram matrix a^T * a.
+///
+/// The Gram matrix a * a^T can be obtained by passing `a.transpose_view()`.
+/// The storage of a is not converted, but both its rows and columns are
+/// needed, so its pattern is indexed in the other storage by
+/// `other_storage_index`. When the Gram matrix is computed repeatedly for a
+/// fixed pattern of a, the index can be computed once and given to
+/// `gram_indexed`.
+///
+/// The result is in CSC storage. Only the entries on or above the diagonal
+/// are computed, which is about half the flops of the plain product. With
+/// `Triangle::Upper`, they are returned as is, which is what the lower
+/// level `ldl_symbolic` and `ldl_numeric` functions read. With
+/// `Triangle::Full`, they are mirrored below the diagonal, so that the
+/// result is exactly symmetric and can be used by `is_symmetric` and
+/// `LdlSymbolic`. Numerical zeros are not stored in the result.
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CSR};
+/// use sprs::sparse::prod::{self, Triangle};
+/// use sprs::sparse::linalg::cholesky::LdlNumeric;
+/// let a = CsMat::new_owned(CSR, 3, 2, vec![0, 1, 3, 4], vec![0, 0, 1, 1],
+///                          vec![1., 2., 3., 4.]).unwrap();
+/// let ata = prod::gram(&a, Triangle::Full);
+/// assert!(sprs::sparse::symmetric::is_symmetric(&ata));
+/// let ldl = LdlNumeric::new(&ata).unwrap();
+/// let x = ldl.solve(&vec![1., 2.]);
+/// ```
+pub fn gram<N, Mat>(a: &Mat, triangle: Triangle) -> CsMatOwned<N>
+where N: Num + Copy,
+      Mat: SpMatView<N> {
+    let a = a.borrowed();
+    let index = other_storage_index(&a);
+    gram_impl(a, index.borrowed(), triangle)
+}
+
+/// Compute the Gram matrix a^T * a, using an index of the pattern of a
+/// computed by `other_storage_index`.
+///
+/// See `gram` for details.
+///
+/// # Errors
+///
+/// - `IncompatibleDimensions` if the dimensions of the index are not those
+///   of a
+/// - `IncompatibleStorages` if the index has the storage of a
+/// - `IncompatiblePatterns` if the index and a have different numbers of
+///   nonzeros. Only this count is checked, an index computed for another
+///   pattern with as many nonzeros gives a wrong result, which is only
+///   caught by a debug assertion.
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CSR};
+/// use sprs::sparse::prod::{self, Triangle};
+/// let a = CsMat::new_owned(CSR, 3, 2, vec![0, 1, 3, 4], vec![0, 0, 1, 1],
+///                          vec![1., 2., 3., 4.]).unwrap();
+/// let index = prod::other_storage_index(&a);
+/// let ata = prod::gram_indexed(&a, &index, Triangle::Upper).unwrap();
+/// assert_eq!(ata, prod::gram(&a, Triangle::Upper));
+/// ```
+pub fn gram_indexed<N, Mat, Idx>(a: &Mat,
+                                 index: &Idx,
+                                 triangle: Triangle
+                                ) -> Result<CsMatOwned<N>, SprsError>
+where N: Num + Copy,
+      Mat: SpMatView<N>,
+      Idx: SpMatView<usize> {
+    let a = a.borrowed();
+    let index = index.borrowed();
+    check_storage_index(&a, &index)?;
+    Ok(gram_impl(a, index, triangle))
+}
+
+fn gram_impl<N>(a: CsMatView<N>,
+                index: CsMatView<usize>,
+                triangle: Triangle) -> CsMatOwned<N>
+where N: Num + Copy {
+    let n = a.cols();
+    let (rows, cols) = match a.storage() {
+        CSR => (OuterAccess::direct(&a), OuterAccess::indexed(&index, a.data())),
+        CSC => (OuterAccess::indexed(&index, a.data()), OuterAccess::direct(&a)),
+    };
+
+    let mut spa = SparseAccumulator::new_filled(n, N::zero());
+    let mut indptr = Vec::with_capacity(n + 1);
+    let mut indices = Vec::new();
+    let mut data = Vec::new();
+    indptr.push(0);
+    for col in 0..n {
+        // the column is a combination of the rows of a intersecting the
+        // column, of which only the start is needed for the upper part
+        cols.for_each_nnz(col, |r, a_rcol| {
+            rows.for_each_nnz_until(r, col, |row, a_rrow| {
+                spa.accumulate(row, a_rrow * a_rcol);
+            });
+        });
+        spa.gather_filtered_into(IndexOrdering::Sorted, |val| val != N::zero(),
+                                 &mut indices, &mut data);
+        spa.reset();
+        indptr.push(indices.len());
+    }
+    let upper = CsMatOwned::new_owned(CSC, n, n, indptr, indices, data)
+        .expect("gram: invalid structure");
+    match triangle {
+        Triangle::Upper => upper,
+        Triangle::Full => full_from_upper(&upper),
+    }
+}
+
+/// Build the symmetric matrix whose upper triangle is stored in the
+/// columns of upper, mirroring its values in time proportional to its
+/// number of nonzeros.
+fn full_from_upper<N: Copy>(upper: &CsMatOwned<N>) -> CsMatOwned<N> {
+    let n = upper.cols();
+    let mut counts = vec![0; n];
+    for (col, vec) in upper.outer_iterator() {
+        for &row in vec.indices() {
+            counts[col] += 1;
+            if row < col {
+                counts[row] += 1;
+            }
+        }
+    }
+    let mut indptr = Vec::with_capacity(n + 1);
+    indptr.push(0);
+    for count in counts {
+        let prev = *indptr.last().unwrap();
+        indptr.push(prev + count);
+    }
+    let nnz = indptr[n];
+    let mut next = indptr[..n].to_vec();
+    let mut indices = vec![0; nnz];
+    // position in the data of upper of each entry
+    let mut positions = vec![0; nnz];
+    // the columns are visited in order, thus each column first receives its
+    // own entries, then the mirrored entries in increasing row order
+    for (col, vec) in upper.outer_iterator() {
+        let start = upper.indptr()[col];
+        for (offset, &row) in vec.indices().iter().enumerate() {
+            indices[next[col]] = row;
+            positions[next[col]] = start + offset;
+            next[col] += 1;
+            if row < col {
+                indices[next[row]] = col;
+                positions[next[row]] = start + offset;
+                next[row] += 1;
+            }
+        }
+    }
+    let data = positions.iter().map(|&pos| upper.data()[pos]).collect();
+    CsMatOwned::new_owned(CSC, n, n, indptr, indices, data)
+        .expect("gram: invalid symmetric structure")
+}
+
+/// Multiply the transpose of a sparse matrix with a dense vector and
+/// accumulate the result into another dense vector.
+///
+/// This works for both storages without converting the matrix, by using
+/// its transposed view.
+pub fn transpose_mul_acc_mat_vec<N>(mat: CsMatView<N>,
+                                    in_vec: &[N],
+                                    res_vec: &mut[N]
+                                   ) -> Result<(), SprsError>
+where N: Num + Copy {
+    let mat_t = mat.transpose_view();
+    match mat_t.storage() {
+        CSR => mul_acc_mat_vec_csr(mat_t, in_vec, res_vec),
+        CSC => mul_acc_mat_vec_csc(mat_t, in_vec, res_vec),
+    }
+}
+
+/// Compute the product of the transpose of lhs with rhs.
+///
+/// The result has the storage of lhs.transpose_view(), as would
+/// `&lhs.transpose_view() * &rhs`. The operands are not converted, but
+/// when lhs and rhs share the same storage, the pattern of rhs is indexed
+/// in the other storage by `other_storage_index`, which costs an
+/// allocation and a transposition of the pattern of rhs. When the product
+/// is computed repeatedly for a fixed pattern of rhs, the index can be
+/// computed once and given to `transpose_mul_indexed`.
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CSR};
+/// use sprs::sparse::prod;
+/// let a = CsMat::new_owned(CSR, 3, 2, vec![0, 1, 3, 4], vec![0, 0, 1, 1],
+///                          vec![1., 2., 3., 4.]).unwrap();
+/// // normal equations matrix of a least-squares problem
+/// let ata = prod::transpose_mul(&a, &a).unwrap();
+/// assert_eq!(ata, &a.transpose_view() * &a);
+/// ```
+pub fn transpose_mul<N, Mat1, Mat2>(lhs: &Mat1,
+                                    rhs: &Mat2
+                                   ) -> Result<CsMatOwned<N>, SprsError>
+where
+N: Num + Copy,
+Mat1: SpMatView<N>,
+Mat2: SpMatView<N> {
+    mul_any_storage(lhs.transpose_view(), rhs.borrowed(), None)
+}
+
+/// Compute the product of the transpose of lhs with rhs, using an index
+/// of the pattern of rhs computed by `other_storage_index`.
+///
+/// The index is only read when lhs and rhs share the same storage, in
+/// which case the product allocates nothing but its result.
+///
+/// # Errors
+///
+/// - `IncompatibleDimensions` if the dimensions of the operands do not
+///   match, or if the dimensions of the index are not those of rhs
+/// - `IncompatibleStorages` if the index has the storage of rhs
+/// - `IncompatiblePatterns` if the index and rhs have different numbers
+///   of nonzeros. Only this count is checked, an index computed for
+///   another pattern with as many nonzeros gives a wrong result, which is
+///   only caught by a debug assertion.
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CSR};
+/// use sprs::sparse::prod;
+/// let a = CsMat::new_owned(CSR, 3, 2, vec![0, 1, 3, 4], vec![0, 0, 1, 1],
+///                          vec![1., 2., 3., 4.]).unwrap();
+/// let index = prod::other_storage_index(&a);
+/// let ata = prod::transpose_mul_indexed(&a, &a, &index).unwrap();
+/// assert_eq!(ata, &a.transpose_view() * &a);
+/// // the index stays valid as long as the pattern of a is unchanged
+/// let a = &a * 2.;
+/// let ata = prod::transpose_mul_indexed(&a, &a, &index).unwrap();
+/// assert_eq!(ata, &a.transpose_view() * &a);
+/// ```
+pub fn transpose_mul_indexed<N, Mat1, Mat2, Idx>(lhs: &Mat1,
+                                                 rhs: &Mat2,
+                                                 rhs_index: &Idx
+                                                ) -> Result<CsMatOwned<N>,
+                                                            SprsError>
+where
+N: Num + Copy,
+Mat1: SpMatView<N>,
+Mat2: SpMatView<N>,
+Idx: SpMatView<usize> {
+    mul_any_storage(lhs.transpose_view(), rhs.borrowed(),
+                    Some(rhs_index.borrowed()))
+}
+
+/// Compute the product of lhs with the transpose of rhs.
+///
+/// The result has the storage of lhs, as would
+/// `&lhs * &rhs.transpose_view()`. As in `transpose_mul`, the pattern of
+/// rhs is indexed in the other storage when lhs and rhs share the same
+/// storage, see `mul_transpose_indexed` to reuse this index.
+pub fn mul_transpose<N, Mat1, Mat2>(lhs: &Mat1,
+                                    rhs: &Mat2
+                                   ) -> Result<CsMatOwned<N>, SprsError>
+where
+N: Num + Copy,
+Mat1: SpMatView<N>,
+Mat2: SpMatView<N> {
+    mul_any_storage(lhs.borrowed(), rhs.transpose_view(), None)
+}
+
+/// Compute the product of lhs with the transpose of rhs, using an index
+/// of the pattern of rhs computed by `other_storage_index`.
+///
+/// See `transpose_mul_indexed` for details.
+pub fn mul_transpose_indexed<N, Mat1, Mat2, Idx>(lhs: &Mat1,
+                                                 rhs: &Mat2,
+                                                 rhs_index: &Idx
+                                                ) -> Result<CsMatOwned<N>,
+                                                            SprsError>
+where
+N: Num + Copy,
+Mat1: SpMatView<N>,
+Mat2: SpMatView<N>,
+Idx: SpMatView<usize> {
+    // the index of rhs^T is the transpose of the index of rhs
+    mul_any_storage(lhs.borrowed(), rhs.transpose_view(),
+                    Some(rhs_index.transpose_view()))
+}
+
+/// Product of two matrices of any storages, the result having the storage
+/// of lhs.
+///
+/// When the storages differ, the pattern of rhs is indexed in the storage
+/// of lhs, and its values are read in place. The index is computed if it
+/// is not provided.
+fn mul_any_storage<N>(lhs: CsMatView<N>,
+                      rhs: CsMatView<N>,
+                      rhs_index: Option<CsMatView<usize>>
+                     ) -> Result<CsMatOwned<N>, SprsError>
+where N: Num + Copy {
+    match (lhs.storage(), rhs.storage()) {
+        (CSR, CSR) => {
+            let mut spa = SparseAccumulator::new_filled(rhs.cols(), N::zero());
+            return csr_mul_csr_spa(&lhs, &rhs, &mut spa);
+        }
+        (CSC, CSC) => {
+            let mut spa = SparseAccumulator::new_filled(lhs.rows(), N::zero());
+            return csc_mul_csc_spa(&lhs, &rhs, &mut spa);
+        }
+        _ => (),
+    }
+    if lhs.cols() != rhs.rows() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    let computed_index;
+    let rhs_index = match rhs_index {
+        Some(index) => {
+            check_storage_index(&rhs, &index)?;
+            index
+        }
+        None => {
+            computed_index = other_storage_index(&rhs);
+            computed_index.borrowed()
+        }
+    };
+    let rhs_other = OuterAccess::indexed(&rhs_index, rhs.data());
+    let lhs_outer = OuterAccess::direct(&lhs);
+    match lhs.storage() {
+        CSR => mul_outer_access(&lhs_outer, &rhs_other, lhs.rows(), rhs.cols()),
+        // the transpose of the product is the product of the transposes,
+        // the rows of rhs^T being the columns of rhs and the rows of
+        // lhs^T the columns of lhs
+        CSC => mul_outer_access(&rhs_other, &lhs_outer, rhs.cols(), lhs.rows())
+                   .map(|res_t| res_t.transpose_into()),
+    }
+}
+
+/// Index the pattern of a matrix in the other storage.
+///
+/// The returned matrix has the dimensions of mat and the other storage,
+/// and its values are the positions of the nonzeros in the data of mat.
+/// It gives access to mat in the other storage while reading its values
+/// in place, and can be reused as long as the pattern of mat does not
+/// change, as in `transpose_mul_indexed`.
+///
+/// Computing the index costs a transposition of the pattern of mat.
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CSR};
+/// use sprs::sparse::prod;
+/// let a = CsMat::new_owned(CSR, 2, 2, vec![0, 2, 3], vec![0, 1, 0],
+///                          vec![1., 2., 3.]).unwrap();
+/// let index = prod::other_storage_index(&a);
+/// assert!(index.is_csc());
+/// // the first column of a holds a[0, 0] and a[1, 0]
+/// assert_eq!(index.outer_view(0).unwrap().data(), &[0, 2]);
+/// ```
+pub fn other_storage_index<N, Mat>(mat: &Mat) -> CsMatOwned<usize>
+where N: Copy,
+      Mat: SpMatView<N> {
+    let mat = mat.borrowed();
+    let positions: Vec<usize> = (0..mat.indices().len()).collect();
+    let pattern = utils::csmat_borrowed_uchk(mat.storage(),
+                                             mat.rows(), mat.cols(),
+                                             mat.indptr(), mat.indices(),
+                                             &positions);
+    pattern.to_other_storage()
+}
+
+/// Check that index can be an index of mat computed by
+/// `other_storage_index`. Only the number of nonzeros of the patterns is
+/// compared, their equality being a debug assertion.
+fn check_storage_index<N>(mat: &CsMatView<N>,
+                          index: &CsMatView<usize>) -> Result<(), SprsError>
+where N: Copy {
+    if index.rows() != mat.rows() || index.cols() != mat.cols() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if index.storage() == mat.storage() {
+        return Err(SprsError::IncompatibleStorages);
+    }
+    if index.nb_nonzero() != mat.nb_nonzero() {
+        return Err(SprsError::IncompatiblePatterns);
+    }
+    debug_assert!(indexes_pattern(mat, index),
+                  "index computed for another pattern");
+    Ok(())
+}
+
+/// Check that each entry of index points to the entry of mat at the same
+/// location. As both have the same number of nonzeros, this means index
+/// holds the pattern of mat in the other storage.
+fn indexes_pattern<N>(mat: &CsMatView<N>, index: &CsMatView<usize>) -> bool
+where N: Copy {
+    let nnz = mat.indices().len();
+    index.outer_iterator().all(|(outer, vec)| {
+        vec.iter().all(|(inner, pos)| {
+            pos < nnz
+            && mat.indices()[pos] == outer
+            && mat.indptr()[inner] <= pos
+            && pos < mat.indptr()[inner + 1]
+        })
+    })
+}
+
+/// Access to the outer dimensions of a compressed matrix, either directly
+/// or through an index of its pattern in the other storage
+struct OuterAccess<'a, N: 'a> {
+    indptr: &'a [usize],
+    indices: &'a [usize],
+    positions: Option<&'a [usize]>,
+    data: &'a [N],
+}
+
+impl<'a, N: 'a + Copy> OuterAccess<'a, N> {
+    fn direct(mat: &'a CsMatView<N>) -> OuterAccess<'a, N> {
+        OuterAccess {
+            indptr: mat.indptr(),
+            indices: mat.indices(),
+            positions: None,
+            data: mat.data(),
+        }
+    }
+
+    fn indexed<IpS, IS, DS>(index: &'a CsMat<usize, IpS, IS, DS>,
+                            data: &'a [N]) -> OuterAccess<'a, N>
+    where IpS: Deref<Target = [usize]>,
+          IS: Deref<Target = [usize]>,
+          DS: Deref<Target = [usize]> {
+        OuterAccess {
+            indptr: index.indptr(),
+            indices: index.indices(),
+            positions: Some(index.data()),
+            data,
+        }
+    }
+
+    /// Call f on the (inner index, value) pairs of the outer dimension k
+    fn for_each_nnz<F>(&self, k: usize, f: F)
+    where F: FnMut(usize, N) {
+        self.for_each_in(self.indptr[k]..self.indptr[k + 1], f);
     }
-    if !rhs.is_csr() {
-        return Err(SprsError::BadStorageType);
+
+    /// Call f on the (inner index, value) pairs of the outer dimension k
+    /// whose inner index is not greater than last
+    fn for_each_nnz_until<F>(&self, k: usize, last: usize, f: F)
+    where F: FnMut(usize, N) {
+        let start = self.indptr[k];
+        let stop = self.indptr[k + 1];
+        let len = match self.indices[start..stop].binary_search_by(|&ind| {
+            if ind <= last { cmp::Ordering::Less }
+            else { cmp::Ordering::Greater }
+        }) {
+            Ok(pos) | Err(pos) => pos,
+        };
+        self.for_each_in(start..start + len, f);
     }
 
-    let mut res = CsMatOwned::empty(lhs.storage(), res_cols);
-    res.reserve_nnz_exact(lhs.nb_nonzero() + rhs.nb_nonzero());
-    for (_, lvec) in lhs.outer_iterator() {
-        // reset the accumulators
-        for wval in workspace.iter_mut() {
-            *wval = N::zero();
-        }
-        // accumulate the resulting row
-        for (lcol, lval) in lvec.iter() {
-            // we can't be out of bounds thanks to the checks of dimension
-            // compatibility and the structure check of CsMat. Therefore it
-            // should be safe to call into an unsafe version of outer_view
-            let rvec = rhs.outer_view(lcol).unwrap();
-            for (rcol, rval) in rvec.iter() {
-                let wval = &mut workspace[rcol];
-                let prod = lval * rval;
-                *wval = *wval + prod;
+    fn for_each_in<F>(&self, range: Range<usize>, mut f: F)
+    where F: FnMut(usize, N) {
+        match self.positions {
+            Some(positions) => {
+                for (&ind, &pos) in self.indices[range.clone()].iter()
+                                        .zip(positions[range].iter()) {
+                    f(ind, self.data[pos]);
+                }
+            }
+            None => {
+                for (&ind, &val) in self.indices[range.clone()].iter()
+                                        .zip(self.data[range].iter()) {
+                    f(ind, val);
+                }
             }
         }
-        // compress the row into the resulting matrix
-        res = res.append_outer(&workspace);
     }
-    // TODO: shrink res storage? would need methods on CsMatOwned
-    assert_eq!(res_rows, res.rows());
-    Ok(res)
+}
+
+/// Row by row product using a sparse accumulator, with row access into
+/// the operands given by OuterAccess. The result is in CSR storage.
+fn mul_outer_access<N>(lhs: &OuterAccess<N>,
+                       rhs: &OuterAccess<N>,
+                       rows: usize,
+                       cols: usize
+                      ) -> Result<CsMatOwned<N>, SprsError>
+where N: Num + Copy {
+    let mut spa = SparseAccumulator::new_filled(cols, N::zero());
+    let mut indptr = Vec::with_capacity(rows + 1);
+    let mut indices = Vec::new();
+    let mut data = Vec::new();
+    indptr.push(0);
+    for row in 0..rows {
+        lhs.for_each_nnz(row, |lcol, lval| {
+            rhs.for_each_nnz(lcol, |rcol, rval| {
+                spa.accumulate(rcol, lval * rval);
+            });
+        });
+        spa.gather_filtered_into(IndexOrdering::Sorted, |val| val != N::zero(),
+                                 &mut indices, &mut data);
+        spa.reset();
+        indptr.push(indices.len());
+    }
+    CsMatOwned::new_owned(CSR, rows, cols, indptr, indices, data)
 }
 
 /// CSR-vector multiplication
@@ -184,6 +1355,421 @@ where N: Copy + Num {
     Ok(res)
 }
 
+/// CSC-vector multiplication using a sparse accumulator.
+///
+/// Only the columns of lhs selected by the non zeros of rhs are visited,
+/// which makes this product much cheaper than `csr_mul_csvec` when rhs is
+/// very sparse.
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CsVec, CSC};
+/// use sprs::sparse::prod;
+/// let a = CsMat::new_owned(CSC, 3, 3, vec![0, 2, 3, 3], vec![1, 2, 0],
+///                          vec![1., 2., 3.]).unwrap();
+/// let x = CsVec::new_owned(3, vec![0], vec![2.]).unwrap();
+/// let y = prod::csc_mul_csvec(a.borrowed(), x.borrowed()).unwrap();
+/// assert_eq!(y.indices(), &[1, 2]);
+/// assert_eq!(y.data(), &[2., 4.]);
+/// ```
+pub fn csc_mul_csvec<N>(lhs: CsMatView<N>,
+                        rhs: CsVecView<N>) -> Result<CsVecOwned<N>, SprsError>
+where N: Copy + Num {
+    let mut spa = SparseAccumulator::new_filled(lhs.rows(), N::zero());
+    csc_mul_csvec_spa(lhs, rhs, &mut spa)
+}
+
+/// CSC-vector multiplication using a caller-owned sparse accumulator.
+///
+/// This performs the same product as `csc_mul_csvec`, but the
+/// accumulator can be reused across products, which makes the cost of
+/// a product independent of the number of rows of lhs. The accumulator
+/// is reset before use, in time proportional to its number of non zeros.
+///
+/// # Errors
+///
+/// - `IncompatibleDimensions` if the dimension of rhs is not the number
+///   of columns of lhs
+/// - `BadStorageType` if lhs is not CSC
+/// - `BadWorkspaceDimensions` if the dimension of spa is not the number
+///   of rows of lhs
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CsVec, CSC, SparseAccumulator};
+/// use sprs::sparse::prod;
+/// let a = CsMat::new_owned(CSC, 3, 3, vec![0, 2, 3, 3], vec![1, 2, 0],
+///                          vec![1., 2., 3.]).unwrap();
+/// let mut spa = SparseAccumulator::new(3);
+/// let x = CsVec::new_owned(3, vec![0], vec![2.]).unwrap();
+/// let y = prod::csc_mul_csvec_spa(a.borrowed(), x.borrowed(), &mut spa)
+///             .unwrap();
+/// assert_eq!(y.indices(), &[1, 2]);
+/// let x = CsVec::new_owned(3, vec![1], vec![1.]).unwrap();
+/// let y = prod::csc_mul_csvec_spa(a.borrowed(), x.borrowed(), &mut spa)
+///             .unwrap();
+/// assert_eq!(y.indices(), &[0]);
+/// assert_eq!(y.data(), &[3.]);
+/// ```
+pub fn csc_mul_csvec_spa<N>(lhs: CsMatView<N>,
+                            rhs: CsVecView<N>,
+                            spa: &mut SparseAccumulator<N>
+                           ) -> Result<CsVecOwned<N>, SprsError>
+where N: Copy + Num {
+    csc_mul_csvec_impl(&PlusTimes, lhs, rhs, spa, |val| val != N::zero())
+}
+
+/// CSC-vector multiplication using the operations of a semiring.
+///
+/// The result stores the entries reached from the pattern of rhs,
+/// whatever their value. With the `OrAnd` semiring, this computes
+/// one step of a breadth first search.
+pub fn csc_mul_csvec_semiring<N, S>(semiring: &S,
+                                    lhs: CsMatView<N>,
+                                    rhs: CsVecView<N>
+                                   ) -> Result<CsVecOwned<N>, SprsError>
+where N: Copy,
+      S: Semiring<N> {
+    let mut spa = SparseAccumulator::new_filled(lhs.rows(), semiring.zero());
+    csc_mul_csvec_semiring_spa(semiring, lhs, rhs, &mut spa)
+}
+
+/// CSC-vector multiplication using the operations of a semiring and a
+/// caller-owned sparse accumulator, see `csc_mul_csvec_spa`.
+pub fn csc_mul_csvec_semiring_spa<N, S>(semiring: &S,
+                                        lhs: CsMatView<N>,
+                                        rhs: CsVecView<N>,
+                                        spa: &mut SparseAccumulator<N>
+                                       ) -> Result<CsVecOwned<N>, SprsError>
+where N: Copy,
+      S: Semiring<N> {
+    csc_mul_csvec_impl(semiring, lhs, rhs, spa, |_| true)
+}
+
+fn csc_mul_csvec_impl<N, S, F>(semiring: &S,
+                               lhs: CsMatView<N>,
+                               rhs: CsVecView<N>,
+                               spa: &mut SparseAccumulator<N>,
+                               keep: F) -> Result<CsVecOwned<N>, SprsError>
+where N: Copy,
+      S: Semiring<N>,
+      F: Fn(N) -> bool {
+    if lhs.cols() != rhs.dim() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if !lhs.is_csc() {
+        return Err(SprsError::BadStorageType);
+    }
+    if spa.dim() != lhs.rows() {
+        return Err(SprsError::BadWorkspaceDimensions);
+    }
+    spa.reset();
+    for (&col, &rval) in rhs.indices().iter().zip(rhs.data().iter()) {
+        let start = lhs.indptr()[col];
+        let stop = lhs.indptr()[col + 1];
+        for (&row, &lval) in lhs.indices()[start..stop].iter()
+                                .zip(lhs.data()[start..stop].iter()) {
+            let prod = semiring.mul(lval, rval);
+            spa.accumulate_with(row, prod, |acc, prod| semiring.add(acc, prod));
+        }
+    }
+    let mut res = spa.gather();
+    res.retain(|_, val| keep(val));
+    Ok(res)
+}
+
+/// Multiply a sparse CSR matrix with a dense vector using the operations
+/// of a semiring, and accumulate the result into another dense vector
+/// using the semiring addition.
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CSR};
+/// use sprs::sparse::prod;
+/// use sprs::sparse::semiring::MinPlus;
+/// // a[i, j] is the weight of the edge from j to i:
+/// // 0 -> 1 (5.), 0 -> 2 (1.) and 1 -> 2 (2.)
+/// let a = CsMat::new_owned(CSR, 3, 3, vec![0, 0, 1, 3], vec![0, 0, 1],
+///                          vec![5., 1., 2.]).unwrap();
+/// let inf = std::f64::INFINITY;
+/// let dist = vec![0., inf, inf];
+/// let mut next = dist.clone();
+/// prod::mul_acc_mat_vec_csr_semiring(&MinPlus, a.borrowed(),
+///                                    &dist, &mut next).unwrap();
+/// assert_eq!(next, vec![0., 5., 1.]);
+/// ```
+pub fn mul_acc_mat_vec_csr_semiring<N, S>(semiring: &S,
+                                          mat: CsMatView<N>,
+                                          in_vec: &[N],
+                                          res_vec: &mut[N]
+                                         ) -> Result<(), SprsError>
+where N: Copy,
+      S: Semiring<N> {
+    if mat.cols() != in_vec.len() || mat.rows() != res_vec.len() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if !mat.is_csr() {
+        return Err(SprsError::IncompatibleStorages);
+    }
+
+    for (row_ind, vec) in mat.outer_iterator() {
+        for (col_ind, value) in vec.iter() {
+            let prod = semiring.mul(value, in_vec[col_ind]);
+            res_vec[row_ind] = semiring.add(res_vec[row_ind], prod);
+        }
+    }
+    Ok(())
+}
+
+/// CSR-vector multiplication using the operations of a semiring.
+///
+/// The result stores the entries whose row intersects the pattern of rhs,
+/// whatever their value.
+pub fn csr_mul_csvec_semiring<N, S>(semiring: &S,
+                                    lhs: CsMatView<N>,
+                                    rhs: CsVecView<N>
+                                   ) -> Result<CsVecOwned<N>, SprsError>
+where N: Copy,
+      S: Semiring<N> {
+    if lhs.cols() != rhs.dim() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if !lhs.is_csr() {
+        return Err(SprsError::BadStorageType);
+    }
+    let mut res = CsVecOwned::empty(lhs.rows());
+    for (row_ind, lvec) in lhs.outer_iterator() {
+        let val = lvec.iter().nnz_zip(rhs.iter()).fold(None, |acc, (_, l, r)| {
+            let prod = semiring.mul(l, r);
+            match acc {
+                Some(acc) => Some(semiring.add(acc, prod)),
+                None => Some(prod),
+            }
+        });
+        if let Some(val) = val {
+            res.append(row_ind, val);
+        }
+    }
+    Ok(res)
+}
+
+/// CSR-CSR multiplication using the operations of a semiring.
+///
+/// The result has the structural pattern of the product: an entry is
+/// stored whenever at least one product contributes to it, whatever its
+/// value.
+///
+/// # Example
+///
+/// ```rust
+/// use sprs::{CsMat, CSR};
+/// use sprs::sparse::prod;
+/// use sprs::sparse::semiring::OrAnd;
+/// // 0 -> 1 -> 2
+/// let a = CsMat::new_owned(CSR, 3, 3, vec![0, 1, 2, 2], vec![1, 2],
+///                          vec![true, true]).unwrap();
+/// let two_steps = prod::csr_mul_csr_semiring(&OrAnd, &a, &a).unwrap();
+/// assert_eq!(two_steps.indptr(), &[0, 1, 1, 1]);
+/// assert_eq!(two_steps.indices(), &[2]);
+/// ```
+pub fn csr_mul_csr_semiring<N, S, Mat1, Mat2>(semiring: &S,
+                                              lhs: &Mat1,
+                                              rhs: &Mat2
+                                             ) -> Result<CsMatOwned<N>,
+                                                         SprsError>
+where N: Copy,
+      S: Semiring<N>,
+      Mat1: SpMatView<N>,
+      Mat2: SpMatView<N> {
+    let lhs = lhs.borrowed();
+    let rhs = rhs.borrowed();
+    check_csr_mul_csr(&lhs, &rhs)?;
+
+    let mut spa = SparseAccumulator::new_filled(rhs.cols(), semiring.zero());
+    let mut indptr = Vec::with_capacity(lhs.rows() + 1);
+    let mut indices = Vec::new();
+    let mut data = Vec::new();
+    indptr.push(0);
+    for (_, lvec) in lhs.outer_iterator() {
+        accumulate_row_product(semiring, lvec, &rhs, &mut spa);
+        spa.gather_into(IndexOrdering::Sorted, &mut indices, &mut data);
+        spa.reset();
+        indptr.push(indices.len());
+    }
+    CsMatOwned::new_owned(CSR, lhs.rows(), rhs.cols(), indptr, indices, data)
+}
+
+/// Selection of the output entries computed by a masked product
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MaskKind {
+    /// Only compute the entries in the pattern of the mask
+    Structure,
+    /// Only compute the entries outside the pattern of the mask
+    Complement,
+}
+
+/// Multiply a sparse CSR matrix with a dense vector and accumulate the result
+/// into another dense vector, restricted to the rows selected by a mask.
+///
+/// The values of the mask are ignored, only its pattern is used. The rows of
+/// res_vec not selected by the mask are left untouched.
+pub fn mul_acc_mat_vec_csr_masked<N, M>(mat: CsMatView<N>,
+                                        in_vec: &[N],
+                                        res_vec: &mut[N],
+                                        mask: CsVecView<M>,
+                                        kind: MaskKind
+                                       ) -> Result<(), SprsError>
+where N: Num + Copy,
+      M: Copy {
+    if mat.cols() != in_vec.len() || mat.rows() != res_vec.len() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if mask.dim() != mat.rows() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if !mat.is_csr() {
+        return Err(SprsError::IncompatibleStorages);
+    }
+
+    let mut row_product = |row_ind: usize| {
+        for (col_ind, value) in mat.outer_view(row_ind).unwrap().iter() {
+            res_vec[row_ind] = res_vec[row_ind] + in_vec[col_ind] * value;
+        }
+    };
+    match kind {
+        MaskKind::Structure => {
+            for &row_ind in mask.indices() {
+                row_product(row_ind);
+            }
+        }
+        MaskKind::Complement => {
+            let mut masked = mask.indices().iter().peekable();
+            for row_ind in 0..mat.rows() {
+                if masked.peek() == Some(&&row_ind) {
+                    masked.next();
+                    continue;
+                }
+                row_product(row_ind);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// CSR-CSR multiplication restricted to the entries selected by a mask.
+///
+/// The values of the mask are ignored, only its pattern is used. The
+/// products contributing to entries outside the selection are skipped,
+/// which makes this much cheaper than computing the full product and
+/// filtering it afterwards. Numerical zeros are not stored in the result.
+///
+/// # Example
+///
+/// Triangle counting in an undirected graph, using the strictly lower
+/// triangular part l of its adjacency matrix: the number of triangles is
+/// the sum of the entries of l .* (l * l).
+///
+/// ```rust
+/// use sprs::{CsMat, CSR};
+/// use sprs::sparse::prod::{self, MaskKind};
+/// // complete graph on 4 vertices
+/// let l = CsMat::new_owned(CSR, 4, 4, vec![0, 0, 1, 3, 6],
+///                          vec![0, 0, 1, 0, 1, 2], vec![1; 6]).unwrap();
+/// let c = prod::csr_mul_csr_masked(&l, &l, &l, MaskKind::Structure)
+///     .unwrap();
+/// assert_eq!(c.data().iter().sum::<i32>(), 4);
+/// ```
+pub fn csr_mul_csr_masked<N, M, Mat1, Mat2, Mat3>(lhs: &Mat1,
+                                                  rhs: &Mat2,
+                                                  mask: &Mat3,
+                                                  kind: MaskKind
+                                                 ) -> Result<CsMatOwned<N>,
+                                                             SprsError>
+where N: Num + Copy,
+      M: Copy,
+      Mat1: SpMatView<N>,
+      Mat2: SpMatView<N>,
+      Mat3: SpMatView<M> {
+    let lhs = lhs.borrowed();
+    let rhs = rhs.borrowed();
+    let mask = mask.borrowed();
+    check_csr_mul_csr(&lhs, &rhs)?;
+    if mask.rows() != lhs.rows() || mask.cols() != rhs.cols() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if !mask.is_csr() {
+        return Err(SprsError::BadStorageType);
+    }
+
+    let cols = rhs.cols();
+    let mut spa = SparseAccumulator::new_filled(cols, N::zero());
+    // masked[col] == row means col is in the pattern of the mask's row
+    let mut masked = vec![usize::MAX; cols];
+    let mut indptr = Vec::with_capacity(lhs.rows() + 1);
+    let mut indices = Vec::new();
+    let mut data = Vec::new();
+    indptr.push(0);
+    let keep_masked = kind == MaskKind::Structure;
+    for ((row, lvec), (_, mvec)) in lhs.outer_iterator()
+                                       .zip(mask.outer_iterator()) {
+        for &col in mvec.indices() {
+            masked[col] = row;
+        }
+        for (lcol, lval) in lvec.iter() {
+            let rvec = rhs.outer_view(lcol).unwrap();
+            for (rcol, rval) in rvec.iter() {
+                if (masked[rcol] == row) == keep_masked {
+                    spa.accumulate(rcol, lval * rval);
+                }
+            }
+        }
+        if keep_masked {
+            // the mask row gives the sorted pattern without sorting
+            for &col in mvec.indices() {
+                match spa.get(col) {
+                    Some(val) if val != N::zero() => {
+                        indices.push(col);
+                        data.push(val);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        else {
+            spa.gather_filtered_into(IndexOrdering::Sorted,
+                                     |val| val != N::zero(),
+                                     &mut indices, &mut data);
+        }
+        spa.reset();
+        indptr.push(indices.len());
+    }
+    CsMatOwned::new_owned(CSR, lhs.rows(), rhs.cols(), indptr, indices, data)
+}
+
+/// CSC-CSC multiplication restricted to the entries selected by a CSC mask.
+///
+/// See `csr_mul_csr_masked`, which this function invokes using free
+/// transposition.
+pub fn csc_mul_csc_masked<N, M, Mat1, Mat2, Mat3>(lhs: &Mat1,
+                                                  rhs: &Mat2,
+                                                  mask: &Mat3,
+                                                  kind: MaskKind
+                                                 ) -> Result<CsMatOwned<N>,
+                                                             SprsError>
+where N: Num + Copy,
+      M: Copy,
+      Mat1: SpMatView<N>,
+      Mat2: SpMatView<N>,
+      Mat3: SpMatView<M> {
+    csr_mul_csr_masked(&rhs.transpose_view(),
+                       &lhs.transpose_view(),
+                       &mask.transpose_view(),
+                       kind).map(|res| res.transpose_into())
+}
+
 /// CSR-dense rowmaj multiplication
 /// 
 /// Performs better if out is rowmaj.
@@ -263,11 +1849,164 @@ pub fn csc_mulacc_dense_rowmaj<'a, N: 'a + Num + Copy>(
         return Err(SprsError::BadStorageType);
     }
 
-    let axis0 = tensor::Axis(0);
-    for ((_, lcol), rline) in lhs.outer_iterator().zip(rhs.iter_axis(axis0)) {
-        for (orow, lval) in lcol.iter() {
-            let mut oline = out.slice_dim_mut(axis0, orow);
-            for (oval, &rval) in oline.iter_mut().zip(rline.iter()) {
+    let axis0 = tensor::Axis(0);
+    for ((_, lcol), rline) in lhs.outer_iterator().zip(rhs.iter_axis(axis0)) {
+        for (orow, lval) in lcol.iter() {
+            let mut oline = out.slice_dim_mut(axis0, orow);
+            for (oval, &rval) in oline.iter_mut().zip(rline.iter()) {
+                let prev = *oval;
+                *oval = prev + lval * rval;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// CSC-dense colmaj multiplication
+/// 
+/// Performs better if out is colmaj
+pub fn csc_mulacc_dense_colmaj<'a, N: 'a + Num + Copy>(
+    lhs: CsMatView<N>, rhs: MatView<N>, mut out: MatViewMut<'a, N>)
+-> Result<(), SprsError> {
+    if lhs.cols() != rhs.rows() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if lhs.rows() != out.rows() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if rhs.cols() != out.cols() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if !lhs.is_csc() {
+        return Err(SprsError::BadStorageType);
+    }
+    if rhs.ordering() != StorageOrder::F {
+        return Err(SprsError::BadStorageType);
+    }
+    let axis1 = tensor::Axis(1);
+    for (mut ocol, rcol) in out.iter_axis_mut(axis1).zip(rhs.iter_axis(axis1)) {
+        for (rrow, lcol) in lhs.outer_iterator() {
+            let rval = rcol[[rrow]];
+            for (orow, lval) in lcol.iter() {
+                let prev = ocol[[orow]];
+                ocol[[orow]] = prev + lval * rval;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// CSR-dense colmaj multiplication
+/// 
+/// Performs better if out is colmaj
+pub fn csr_mulacc_dense_colmaj<'a, N: 'a + Num + Copy>(
+    lhs: CsMatView<N>, rhs: MatView<N>, mut out: MatViewMut<'a, N>)
+-> Result<(), SprsError> {
+    if lhs.cols() != rhs.rows() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if lhs.rows() != out.rows() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if rhs.cols() != out.cols() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if !lhs.is_csr() {
+        return Err(SprsError::BadStorageType);
+    }
+    if rhs.ordering() != StorageOrder::F {
+        return Err(SprsError::BadStorageType);
+    }
+    let axis1 = tensor::Axis(1);
+    for (mut ocol, rcol) in out.iter_axis_mut(axis1).zip(rhs.iter_axis(axis1)) {
+        for (orow, lrow) in lhs.outer_iterator() {
+            let mut prev = ocol[[orow]];
+            for (rrow, lval) in lrow.iter() {
+                let rval = rcol[[rrow]];
+                prev = prev + lval * rval;
+            }
+            ocol[[orow]] = prev;
+        }
+    }
+    Ok(())
+}
+
+/// Dense rowmaj-CSR multiplication
+///
+/// Performs better if out is rowmaj.
+pub fn dense_rowmaj_mulacc_csr<'a, N: 'a + Num + Copy>(
+    lhs: MatView<N>, rhs: CsMatView<N>, mut out: MatViewMut<'a, N>)
+-> Result<(), SprsError> {
+    check_dense_mulacc_sparse(&lhs, &rhs, out.shape(),
+                              StorageOrder::C, CSR)?;
+    for i in 0..lhs.rows() {
+        for (k, rline) in rhs.outer_iterator() {
+            let lval = lhs[[i, k]];
+            for (j, rval) in rline.iter() {
+                let prev = out[[i, j]];
+                out[[i, j]] = prev + lval * rval;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Dense colmaj-CSR multiplication
+///
+/// Performs better if out is colmaj.
+pub fn dense_colmaj_mulacc_csr<'a, N: 'a + Num + Copy>(
+    lhs: MatView<N>, rhs: CsMatView<N>, mut out: MatViewMut<'a, N>)
+-> Result<(), SprsError> {
+    check_dense_mulacc_sparse(&lhs, &rhs, out.shape(),
+                              StorageOrder::F, CSR)?;
+    let axis1 = tensor::Axis(1);
+    for ((_, rline), lcol) in rhs.outer_iterator().zip(lhs.iter_axis(axis1)) {
+        for (j, rval) in rline.iter() {
+            let mut ocol = out.slice_dim_mut(axis1, j);
+            for (oval, &lval) in ocol.iter_mut().zip(lcol.iter()) {
+                let prev = *oval;
+                *oval = prev + lval * rval;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Dense rowmaj-CSC multiplication
+///
+/// Performs better if out is rowmaj.
+pub fn dense_rowmaj_mulacc_csc<'a, N: 'a + Num + Copy>(
+    lhs: MatView<N>, rhs: CsMatView<N>, mut out: MatViewMut<'a, N>)
+-> Result<(), SprsError> {
+    check_dense_mulacc_sparse(&lhs, &rhs, out.shape(),
+                              StorageOrder::C, CSC)?;
+    let axis0 = tensor::Axis(0);
+    for (i, lline) in lhs.iter_axis(axis0).enumerate() {
+        for (j, rcol) in rhs.outer_iterator() {
+            let mut prev = out[[i, j]];
+            for (k, rval) in rcol.iter() {
+                prev = prev + lline[[k]] * rval;
+            }
+            out[[i, j]] = prev;
+        }
+    }
+    Ok(())
+}
+
+/// Dense colmaj-CSC multiplication
+///
+/// Performs better if out is colmaj.
+pub fn dense_colmaj_mulacc_csc<'a, N: 'a + Num + Copy>(
+    lhs: MatView<N>, rhs: CsMatView<N>, mut out: MatViewMut<'a, N>)
+-> Result<(), SprsError> {
+    check_dense_mulacc_sparse(&lhs, &rhs, out.shape(),
+                              StorageOrder::F, CSC)?;
+    let axis1 = tensor::Axis(1);
+    for (mut ocol, (_, rcol)) in out.iter_axis_mut(axis1)
+                                    .zip(rhs.outer_iterator()) {
+        for (k, rval) in rcol.iter() {
+            let lcol = lhs.slice_dim(axis1, k);
+            for (oval, &lval) in ocol.iter_mut().zip(lcol.iter()) {
                 let prev = *oval;
                 *oval = prev + lval * rval;
             }
@@ -276,84 +2015,306 @@ pub fn csc_mulacc_dense_rowmaj<'a, N: 'a + Num + Copy>(
     Ok(())
 }
 
-/// CSC-dense colmaj multiplication
-/// 
-/// Performs better if out is colmaj
-pub fn csc_mulacc_dense_colmaj<'a, N: 'a + Num + Copy>(
-    lhs: CsMatView<N>, rhs: MatView<N>, mut out: MatViewMut<'a, N>)
--> Result<(), SprsError> {
+fn check_dense_mulacc_sparse<N>(lhs: &MatView<N>,
+                                rhs: &CsMatView<N>,
+                                out_shape: [usize; 2],
+                                lhs_ordering: StorageOrder,
+                                rhs_storage: CompressedStorage
+                               ) -> Result<(), SprsError>
+where N: Copy {
     if lhs.cols() != rhs.rows() {
         return Err(SprsError::IncompatibleDimensions);
     }
-    if lhs.rows() != out.rows() {
+    if lhs.rows() != out_shape[0] {
         return Err(SprsError::IncompatibleDimensions);
     }
-    if rhs.cols() != out.cols() {
+    if rhs.cols() != out_shape[1] {
         return Err(SprsError::IncompatibleDimensions);
     }
-    if !lhs.is_csc() {
+    if rhs.storage() != rhs_storage {
         return Err(SprsError::BadStorageType);
     }
-    if rhs.ordering() != StorageOrder::F {
+    if lhs.ordering() != lhs_ordering {
         return Err(SprsError::BadStorageType);
     }
-    let axis1 = tensor::Axis(1);
-    for (mut ocol, rcol) in out.iter_axis_mut(axis1).zip(rhs.iter_axis(axis1)) {
-        for (rrow, lcol) in lhs.outer_iterator() {
-            let rval = rcol[[rrow]];
-            for (orow, lval) in lcol.iter() {
-                let prev = ocol[[orow]];
-                ocol[[orow]] = prev + lval * rval;
+    Ok(())
+}
+
+/// Number of chunks the parallel products split their work into.
+///
+/// Using more chunks than threads lets rayon balance the load when
+/// the cost of the rows is not exactly proportional to their nnz.
+#[cfg(feature = "rayon")]
+fn nb_par_chunks() -> usize {
+    4 * rayon::current_num_threads()
+}
+
+/// Split the outer dimension of a compressed matrix into at most nb_chunks
+/// contiguous ranges holding roughly the same number of nonzeros.
+///
+/// Returns the boundaries of the ranges, starting with 0 and ending with
+/// the outer dimension.
+#[cfg(feature = "rayon")]
+fn nnz_balanced_chunks(indptr: &[usize], nb_chunks: usize) -> Vec<usize> {
+    let outer = indptr.len() - 1;
+    let start = indptr[0];
+    let nnz = indptr[outer] - start;
+    let mut bounds = vec![0];
+    for k in 1..nb_chunks {
+        let target = start + nnz / nb_chunks * k;
+        // first outer index whose indptr reaches the target
+        let bound = match indptr.binary_search_by(|&x| {
+            if x < target { cmp::Ordering::Less }
+            else { cmp::Ordering::Greater }
+        }) {
+            Ok(pos) | Err(pos) => cmp::min(pos, outer),
+        };
+        if bound > *bounds.last().unwrap() {
+            bounds.push(bound);
+        }
+    }
+    if outer > *bounds.last().unwrap() {
+        bounds.push(outer);
+    }
+    bounds
+}
+
+/// Split a dimension of length len into at most nb_chunks contiguous
+/// ranges of similar lengths, returning their boundaries.
+#[cfg(feature = "rayon")]
+fn uniform_chunks(len: usize, nb_chunks: usize) -> Vec<usize> {
+    let mut bounds: Vec<_> = (0..nb_chunks + 1).map(|k| k * len / nb_chunks)
+                                               .collect();
+    bounds.dedup();
+    bounds
+}
+
+/// Split data into the mutable chunks spanned by consecutive bounds, each
+/// bound being an index along a dimension of the given stride.
+#[cfg(feature = "rayon")]
+fn split_at_bounds_mut<'a, N>(data: &'a mut [N],
+                              bounds: &[usize],
+                              stride: usize
+                             ) -> Vec<(Range<usize>, &'a mut [N])> {
+    let mut chunks = Vec::with_capacity(bounds.len());
+    let mut rest = data;
+    for window in bounds.windows(2) {
+        let len = cmp::min((window[1] - window[0]) * stride, rest.len());
+        let tmp = rest;
+        let (chunk, tail) = tmp.split_at_mut(len);
+        chunks.push((window[0]..window[1], chunk));
+        rest = tail;
+    }
+    chunks
+}
+
+/// Multiply a sparse CSR matrix with a dense vector and accumulate the result
+/// into another dense vector, in parallel over the rows of the matrix.
+///
+/// The rows are split into chunks of similar nnz, and the result is bitwise
+/// identical to the one of `mul_acc_mat_vec_csr`.
+#[cfg(feature = "rayon")]
+pub fn par_mul_acc_mat_vec_csr<N>(mat: CsMatView<N>,
+                                  in_vec: &[N],
+                                  res_vec: &mut[N]) -> Result<(), SprsError>
+where N: Num + Copy + Send + Sync {
+    if mat.cols() != in_vec.len() || mat.rows() != res_vec.len() {
+        return Err(SprsError::IncompatibleDimensions);
+    }
+    if !mat.is_csr() {
+        return Err(SprsError::IncompatibleStorages);
+    }
+
+    let bounds = nnz_balanced_chunks(mat.indptr(), nb_par_chunks());
+    let chunks = split_at_bounds_mut(res_vec, &bounds, 1);
+    chunks.into_par_iter().for_each(|(rows, res_chunk)| {
+        for (row_ind, res) in rows.zip(res_chunk.iter_mut()) {
+            for (col_ind, value) in mat.outer_view(row_ind).unwrap().iter() {
+                *res = *res + in_vec[col_ind] * value;
             }
         }
+    });
+    Ok(())
+}
+
+/// Parallel CSR-CSR multiplication.
+///
+/// The rows of lhs are split into chunks of similar nnz, each chunk using
+/// its own sparse accumulator of dimension rhs.cols(). The result is bitwise
+/// identical to the one of `csr_mul_csr_spa`.
+#[cfg(feature = "rayon")]
+pub fn par_csr_mul_csr<N, Mat1, Mat2>(lhs: &Mat1,
+                                      rhs: &Mat2
+                                     ) -> Result<CsMatOwned<N>, SprsError>
+where
+N: Num + Copy + Send + Sync,
+Mat1: SpMatView<N>,
+Mat2: SpMatView<N> {
+    let lhs = lhs.borrowed();
+    let rhs = rhs.borrowed();
+    check_csr_mul_csr(&lhs, &rhs)?;
+
+    let bounds = nnz_balanced_chunks(lhs.indptr(), nb_par_chunks());
+    let chunks: Vec<_> = bounds.par_windows(2).map(|window| {
+        let mut spa = SparseAccumulator::new_filled(rhs.cols(), N::zero());
+        let mut row_ends = Vec::with_capacity(window[1] - window[0]);
+        let mut indices = Vec::new();
+        let mut data = Vec::new();
+        for row in window[0]..window[1] {
+            let lvec = lhs.outer_view(row).unwrap();
+            accumulate_row_product(&PlusTimes, lvec, &rhs, &mut spa);
+            spa.gather_filtered_into(IndexOrdering::Sorted,
+                                     |val| val != N::zero(),
+                                     &mut indices, &mut data);
+            spa.reset();
+            row_ends.push(indices.len());
+        }
+        (row_ends, indices, data)
+    }).collect();
+
+    let nnz = chunks.iter().map(|(_, indices, _)| indices.len()).sum();
+    let mut indptr = Vec::with_capacity(lhs.rows() + 1);
+    let mut indices = Vec::with_capacity(nnz);
+    let mut data = Vec::with_capacity(nnz);
+    indptr.push(0);
+    for (row_ends, chunk_indices, chunk_data) in chunks {
+        let offset = indices.len();
+        indptr.extend(row_ends.iter().map(|&end| offset + end));
+        indices.extend_from_slice(&chunk_indices);
+        data.extend_from_slice(&chunk_data);
     }
+    CsMatOwned::new_owned(lhs.storage(), lhs.rows(), rhs.cols(),
+                          indptr, indices, data)
+}
+
+/// Parallel CSR-dense rowmaj multiplication.
+///
+/// The result is bitwise identical to the one of `csr_mulacc_dense_rowmaj`.
+#[cfg(feature = "rayon")]
+pub fn par_csr_mulacc_dense_rowmaj<'a, N>(lhs: CsMatView<N>,
+                                          rhs: MatView<N>,
+                                          out: MatViewMut<'a, N>
+                                         ) -> Result<(), SprsError>
+where N: 'a + Num + Copy + Send + Sync {
+    check_csr_mulacc_dense(&lhs, &rhs, out.shape(), StorageOrder::C)?;
+    par_csr_mulacc_dense_impl(lhs, rhs, out);
     Ok(())
 }
 
-/// CSR-dense colmaj multiplication
-/// 
-/// Performs better if out is colmaj
-pub fn csr_mulacc_dense_colmaj<'a, N: 'a + Num + Copy>(
-    lhs: CsMatView<N>, rhs: MatView<N>, mut out: MatViewMut<'a, N>)
--> Result<(), SprsError> {
+/// Parallel CSR-dense colmaj multiplication.
+///
+/// The result is bitwise identical to the one of `csr_mulacc_dense_colmaj`.
+#[cfg(feature = "rayon")]
+pub fn par_csr_mulacc_dense_colmaj<'a, N>(lhs: CsMatView<N>,
+                                          rhs: MatView<N>,
+                                          out: MatViewMut<'a, N>
+                                         ) -> Result<(), SprsError>
+where N: 'a + Num + Copy + Send + Sync {
+    check_csr_mulacc_dense(&lhs, &rhs, out.shape(), StorageOrder::F)?;
+    par_csr_mulacc_dense_impl(lhs, rhs, out);
+    Ok(())
+}
+
+#[cfg(feature = "rayon")]
+fn check_csr_mulacc_dense<N>(lhs: &CsMatView<N>,
+                             rhs: &MatView<N>,
+                             out_shape: [usize; 2],
+                             rhs_ordering: StorageOrder
+                            ) -> Result<(), SprsError>
+where N: Copy {
     if lhs.cols() != rhs.rows() {
         return Err(SprsError::IncompatibleDimensions);
     }
-    if lhs.rows() != out.rows() {
+    if lhs.rows() != out_shape[0] {
         return Err(SprsError::IncompatibleDimensions);
     }
-    if rhs.cols() != out.cols() {
+    if rhs.cols() != out_shape[1] {
         return Err(SprsError::IncompatibleDimensions);
     }
     if !lhs.is_csr() {
         return Err(SprsError::BadStorageType);
     }
-    if rhs.ordering() != StorageOrder::F {
+    if rhs.ordering() != rhs_ordering {
         return Err(SprsError::BadStorageType);
     }
-    let axis1 = tensor::Axis(1);
-    for (mut ocol, rcol) in out.iter_axis_mut(axis1).zip(rhs.iter_axis(axis1)) {
-        for (orow, lrow) in lhs.outer_iterator() {
-            let mut prev = ocol[[orow]];
-            for (rrow, lval) in lrow.iter() {
-                let rval = rcol[[rrow]];
-                prev = prev + lval * rval;
+    Ok(())
+}
+
+/// Split out into disjoint blocks of rows (or of columns if its rows
+/// are interleaved) and accumulate the product of each block in parallel.
+#[cfg(feature = "rayon")]
+fn par_csr_mulacc_dense_impl<'a, N>(lhs: CsMatView<N>,
+                                    rhs: MatView<N>,
+                                    mut out: MatViewMut<'a, N>)
+where N: 'a + Num + Copy + Send + Sync {
+    let rows = out.rows();
+    let cols = out.cols();
+    let strides = out.strides();
+    if cols <= 1 || strides[1] * (cols - 1) < strides[0] {
+        // the rows of out are disjoint slices of its data
+        let bounds = nnz_balanced_chunks(lhs.indptr(), nb_par_chunks());
+        let chunks = split_at_bounds_mut(out.data_mut(), &bounds, strides[0]);
+        chunks.into_par_iter().for_each(|(block_rows, block)| {
+            csr_mulacc_dense_block(&lhs, &rhs, block_rows, 0..cols,
+                                   block, strides);
+        });
+    }
+    else if rows <= 1 || strides[0] * (rows - 1) < strides[1] {
+        // the columns of out are disjoint slices of its data
+        let bounds = uniform_chunks(cols, nb_par_chunks());
+        let chunks = split_at_bounds_mut(out.data_mut(), &bounds, strides[1]);
+        chunks.into_par_iter().for_each(|(block_cols, block)| {
+            csr_mulacc_dense_block(&lhs, &rhs, 0..rows, block_cols,
+                                   block, strides);
+        });
+    }
+    else {
+        csr_mulacc_dense_block(&lhs, &rhs, 0..rows, 0..cols,
+                               out.data_mut(), strides);
+    }
+}
+
+/// Accumulate the product of lhs and rhs into a block of out, whose data
+/// starts at the element (rows.start, cols.start).
+///
+/// Each element is accumulated in the order of the nonzeros of its row
+/// of lhs, as is done by the sequential products.
+#[cfg(feature = "rayon")]
+fn csr_mulacc_dense_block<N>(lhs: &CsMatView<N>,
+                             rhs: &MatView<N>,
+                             rows: Range<usize>,
+                             cols: Range<usize>,
+                             out: &mut [N],
+                             out_strides: [usize; 2])
+where N: Num + Copy {
+    let origin = rows.start * out_strides[0] + cols.start * out_strides[1];
+    let rdata = rhs.data();
+    let rstrides = rhs.strides();
+    for row in rows {
+        let lrow = lhs.outer_view(row).unwrap();
+        for col in cols.clone() {
+            let oind = row * out_strides[0] + col * out_strides[1] - origin;
+            let mut acc = out[oind];
+            for (k, lval) in lrow.iter() {
+                acc = acc + lval * rdata[k * rstrides[0] + col * rstrides[1]];
             }
-            ocol[[orow]] = prev;
+            out[oind] = acc;
         }
     }
-    Ok(())
 }
 
 #[cfg(test)]
 mod test {
     use sparse::csmat::{CsMat, CsMatOwned};
     use sparse::vec::{CsVec};
+    use sparse::accumulator::SparseAccumulator;
     use sparse::csmat::CompressedStorage::{CSC, CSR};
-    use dense_mats::{MatOwned};
-    use super::{mul_acc_mat_vec_csc, mul_acc_mat_vec_csr, csr_mul_csr,
-                csr_mulacc_dense_rowmaj};
-    use test_data::{mat1, mat2, mat1_self_matprod, mat1_matprod_mat2,
+    use dense_mats::{MatOwned, tensor};
+    use super::{mul_acc_mat_vec_csc, mul_acc_mat_vec_csr, csr_mul_csvec,
+                csr_mulacc_dense_rowmaj, IndexOrdering, MaskKind};
+    use errors::SprsError;
+    use sparse::semiring::{PlusTimes, MinPlus, OrAnd};
+    use test_data::{mat1, mat2, mat3, mat1_self_matprod, mat1_matprod_mat2,
                     mat1_csc, mat4, mat1_csc_matprod_mat4,
                     mat_dense1, mat5, mat_dense2, mat_dense1_colmaj};
 
@@ -402,10 +2363,11 @@ mod test {
     }
 
     #[test]
+    #[allow(deprecated)]
     fn mul_csr_csr_identity() {
         let eye: CsMatOwned<i32> = CsMat::eye(CSR, 10);
         let mut workspace = [0; 10];
-        let res = csr_mul_csr(&eye, &eye, &mut workspace).unwrap();
+        let res = super::csr_mul_csr(&eye, &eye, &mut workspace).unwrap();
         assert_eq!(eye, res);
 
         let res = &eye * &eye;
@@ -448,6 +2410,623 @@ mod test {
         assert_eq!(expected_output, res);
     }
 
+    #[test]
+    fn mul_csr_csr_two_phases() {
+        let a = mat1();
+        let b = mat2();
+        let mut res = super::csr_mul_csr_symbolic(&a, &b).unwrap();
+        let mut spa = SparseAccumulator::new(b.cols());
+        super::csr_mul_csr_numeric(&a, &b, &mut res, &mut spa).unwrap();
+        assert_eq!(res, mat1_matprod_mat2());
+
+        // refill with different values on the same pattern
+        let mut a2 = mat1();
+        a2.scale(2.);
+        super::csr_mul_csr_numeric(&a2, &b, &mut res, &mut spa).unwrap();
+        let mut expected = mat1_matprod_mat2();
+        expected.scale(2.);
+        assert_eq!(res, expected);
+
+        // a product with a pattern not contained in res' is rejected
+        let mut res_aa = super::csr_mul_csr_symbolic(&a, &a).unwrap();
+        let err = super::csr_mul_csr_numeric(&a, &b, &mut res_aa, &mut spa);
+        assert_eq!(err, Err(SprsError::IncompatiblePatterns));
+
+        // the accumulator is still usable after an error
+        super::csr_mul_csr_numeric(&a, &b, &mut res, &mut spa).unwrap();
+        assert_eq!(res, mat1_matprod_mat2());
+
+        let mut small_spa = SparseAccumulator::new(b.cols() - 1);
+        let err = super::csr_mul_csr_numeric(&a, &b, &mut res,
+                                             &mut small_spa);
+        assert_eq!(err, Err(SprsError::BadWorkspaceDimensions));
+    }
+
+    #[test]
+    fn mul_csr_csr_symbolic_keeps_cancellations() {
+        // | 1  1| | 1| = |0|
+        // | 0  1| |-1|   |-1|
+        let a = CsMat::new_owned(CSR, 2, 2, vec![0, 2, 3], vec![0, 1, 1],
+                                 vec![1., 1., 1.]).unwrap();
+        let b = CsMat::new_owned(CSR, 2, 1, vec![0, 1, 2], vec![0, 0],
+                                 vec![1., -1.]).unwrap();
+        let mut res = super::csr_mul_csr_symbolic(&a, &b).unwrap();
+        let mut spa = SparseAccumulator::new(b.cols());
+        super::csr_mul_csr_numeric(&a, &b, &mut res, &mut spa).unwrap();
+        assert_eq!(res.indptr(), &[0, 1, 2]);
+        assert_eq!(res.data(), &[0., -1.]);
+    }
+
+    #[test]
+    fn mul_csc_csc_two_phases() {
+        let a = mat1_csc();
+        let b = mat4();
+        let mut res = super::csc_mul_csc_symbolic(&a, &b).unwrap();
+        assert!(res.is_csc());
+        let mut spa = SparseAccumulator::new(a.rows());
+        super::csc_mul_csc_numeric(&a, &b, &mut res, &mut spa).unwrap();
+        assert_eq!(res, mat1_csc_matprod_mat4());
+
+        let mut res_csr = super::csr_mul_csr_symbolic(&a.to_csr(),
+                                                      &b.to_csr()).unwrap();
+        let err = super::csc_mul_csc_numeric(&a, &b, &mut res_csr, &mut spa);
+        assert_eq!(err, Err(SprsError::BadStorageType));
+    }
+
+    #[test]
+    fn mul_csr_csr_raw_orderings() {
+        let a = mat1();
+        let b = mat2();
+        let mut spa = SparseAccumulator::new(b.cols());
+        let mut indptr = Vec::new();
+        let mut indices = Vec::new();
+        let mut data = Vec::new();
+        super::csr_mul_csr_raw(a.borrowed(), b.borrowed(),
+                               IndexOrdering::Sorted, &mut spa,
+                               &mut indptr, &mut indices, &mut data).unwrap();
+        let expected_output = mat1_matprod_mat2();
+        assert_eq!(indptr, expected_output.indptr());
+        assert_eq!(indices, expected_output.indices());
+        assert_eq!(data, expected_output.data());
+
+        super::csr_mul_csr_raw(a.borrowed(), b.borrowed(),
+                               IndexOrdering::Unsorted, &mut spa,
+                               &mut indptr, &mut indices, &mut data).unwrap();
+        assert_eq!(indptr, expected_output.indptr());
+        for (row, window) in indptr.windows(2).enumerate() {
+            let mut entries: Vec<_> = indices[window[0]..window[1]].iter()
+                .cloned()
+                .zip(data[window[0]..window[1]].iter().cloned())
+                .collect();
+            entries.sort_by_key(|&(ind, _)| ind);
+            let expected: Vec<_> = expected_output.outer_view(row)
+                                                  .unwrap()
+                                                  .iter()
+                                                  .collect();
+            assert_eq!(entries, expected);
+        }
+    }
+
+    #[test]
+    fn mul_csr_csr_many_cols() {
+        // the cost of the product should not depend on the number of columns
+        let n = 1_000_000;
+        let a = CsMat::new_owned(CSR, 2, 2, vec![0, 1, 3],
+                                 vec![1, 0, 1], vec![2., 1., 1.]).unwrap();
+        let b = CsMat::new_owned(CSR, 2, n, vec![0, 2, 3],
+                                 vec![3, n - 1, 3], vec![1., 1., -1.]).unwrap();
+        let c = &a * &b;
+        let expected_output = CsMat::new_owned(CSR, 2, n, vec![0, 1, 2],
+                                               vec![3, n - 1],
+                                               vec![-2., 1.]).unwrap();
+        assert_eq!(c, expected_output);
+    }
+
+    #[test]
+    fn transposed_mat_vec() {
+        let a = mat3();
+        let x = vec![1., 2., 3., 4., 5.];
+        let expected_output = {
+            let mut res = vec![0.; 4];
+            let a_t = a.transpose_view().to_other_storage();
+            mul_acc_mat_vec_csr(a_t.borrowed(), &x, &mut res).unwrap();
+            res
+        };
+        for a in &[a.to_csc(), a] {
+            let mut res = vec![0.; 4];
+            super::transpose_mul_acc_mat_vec(a.borrowed(), &x, &mut res)
+                .unwrap();
+            assert_eq!(res, expected_output);
+        }
+        let mut res = vec![0.; 5];
+        let err = super::transpose_mul_acc_mat_vec(mat3().borrowed(),
+                                                   &x, &mut res);
+        assert_eq!(err, Err(SprsError::IncompatibleDimensions));
+    }
+
+    #[test]
+    fn transposed_products() {
+        let a = mat3();
+        let b = mat5();
+        let c = mat1();
+        for a in &[a.to_csc(), a.to_csr()] {
+            for b in &[b.to_csc(), b.to_csr()] {
+                let res = super::transpose_mul(a, b).unwrap();
+                let expected_output = &a.transpose_view() * b;
+                assert_eq!(res, expected_output);
+            }
+            for c in &[c.to_csc(), c.to_csr()] {
+                let res = super::mul_transpose(c, &a.transpose_view())
+                    .unwrap();
+                assert_eq!(res, c * a);
+                let res = super::mul_transpose(a, c);
+                assert_eq!(res, Err(SprsError::IncompatibleDimensions));
+            }
+        }
+    }
+
+    #[test]
+    fn transposed_products_indexed() {
+        let a = mat3();
+        let b = mat5();
+        let c = mat1();
+        for a in &[a.to_csc(), a.to_csr()] {
+            for b in &[b.to_csc(), b.to_csr()] {
+                let b_index = super::other_storage_index(b);
+                let res = super::transpose_mul_indexed(a, b, &b_index)
+                    .unwrap();
+                assert_eq!(res, &a.transpose_view() * b);
+                // the index only depends on the pattern
+                let b2 = b * 2.;
+                let res = super::transpose_mul_indexed(a, &b2, &b_index)
+                    .unwrap();
+                assert_eq!(res, &a.transpose_view() * &b2);
+            }
+            let a_t = a.transpose_view();
+            let a_t_index = super::other_storage_index(&a_t);
+            for c in &[c.to_csc(), c.to_csr()] {
+                let res = super::mul_transpose_indexed(c, &a_t, &a_t_index)
+                    .unwrap();
+                assert_eq!(res, c * a);
+            }
+        }
+
+        let a = mat3();
+        let index = super::other_storage_index(&a);
+        let res = super::transpose_mul_indexed(&a, &a, &index.to_csr());
+        assert_eq!(res, Err(SprsError::IncompatibleStorages));
+        let res = super::transpose_mul_indexed(&a, &a,
+                                               &index.transpose_view());
+        assert_eq!(res, Err(SprsError::IncompatibleDimensions));
+        let eye: CsMatOwned<f64> = CsMat::eye(CSR, 5);
+        let sparser = CsMat::new_owned(CSR, 5, 5, vec![0, 1, 1, 1, 1, 1],
+                                       vec![0], vec![1.]).unwrap();
+        let index = super::other_storage_index(&sparser);
+        let res = super::transpose_mul_indexed(&c, &eye, &index);
+        assert_eq!(res, Err(SprsError::IncompatiblePatterns));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn storage_index_of_another_pattern() {
+        // same dimensions and number of nonzeros, different patterns
+        let a = CsMat::new_owned(CSR, 2, 2, vec![0, 1, 2], vec![0, 1],
+                                 vec![1., 2.]).unwrap();
+        let b = CsMat::new_owned(CSR, 2, 2, vec![0, 1, 2], vec![1, 0],
+                                 vec![1., 2.]).unwrap();
+        let index = super::other_storage_index(&b);
+        let _ = super::transpose_mul_indexed(&a, &a, &index);
+    }
+
+    #[test]
+    fn semiring_products() {
+        let a = mat1();
+        let res = super::csr_mul_csr_semiring(&PlusTimes, &a, &a).unwrap();
+        assert_eq!(res, mat1_self_matprod());
+
+        // a[i, j] is the weight of the edge from j to i in a directed graph
+        let inf = f64::INFINITY;
+        let a = CsMat::new_owned(CSR, 4, 4,
+                                 vec![0, 1, 2, 4, 5],
+                                 vec![3, 0, 0, 1, 2],
+                                 vec![1., 2., 7., 3., 1.]).unwrap();
+        // shortest paths of length at most two, using a matrix with
+        // explicit zeros on its diagonal to keep the shorter paths
+        let a_eye = CsMat::new_owned(CSR, 4, 4,
+                                     vec![0, 2, 4, 7, 9],
+                                     vec![0, 3, 0, 1, 0, 1, 2, 2, 3],
+                                     vec![0., 1., 2., 0., 7., 3., 0., 1., 0.])
+                         .unwrap();
+        let paths = super::csr_mul_csr_semiring(&MinPlus, &a_eye, &a_eye)
+            .unwrap();
+        let expected_output = CsMat::new_owned(CSR, 4, 4,
+                                               vec![0, 3, 6, 10, 14],
+                                               vec![0, 2, 3,
+                                                    0, 1, 3,
+                                                    0, 1, 2, 3,
+                                                    0, 1, 2, 3],
+                                               vec![0., 2., 1.,
+                                                    2., 0., 3.,
+                                                    5., 3., 0., 8.,
+                                                    8., 4., 1., 0.]).unwrap();
+        assert_eq!(paths, expected_output);
+
+        let dist = vec![0., inf, inf, inf];
+        let mut next = vec![inf; 4];
+        super::mul_acc_mat_vec_csr_semiring(&MinPlus, a.borrowed(),
+                                            &dist, &mut next).unwrap();
+        assert_eq!(next, vec![inf, 2., 7., inf]);
+
+        // vertices reachable in one step from vertex 0
+        let reach = CsMat::new_owned(CSR, 4, 4, a.indptr().to_vec(),
+                                     a.indices().to_vec(),
+                                     vec![true; 5]).unwrap();
+        let start = CsVec::new_owned(4, vec![0], vec![true]).unwrap();
+        let next = super::csr_mul_csvec_semiring(&OrAnd, reach.borrowed(),
+                                                 start.borrowed()).unwrap();
+        assert_eq!(next.indices(), &[1, 2]);
+        let next = super::csr_mul_csvec_semiring(&OrAnd, reach.borrowed(),
+                                                 next.borrowed()).unwrap();
+        assert_eq!(next.indices(), &[2, 3]);
+    }
+
+    #[test]
+    fn masked_products() {
+        let a = mat1();
+        let b = mat2();
+        let full = &a * &b;
+        let mask = mat3_pattern_square();
+        let masked = super::csr_mul_csr_masked(&a, &b, &mask,
+                                               MaskKind::Structure).unwrap();
+        let complement = super::csr_mul_csr_masked(&a, &b, &mask,
+                                                   MaskKind::Complement)
+            .unwrap();
+        for (row, vec) in full.outer_iterator() {
+            for &col in vec.indices() {
+                let in_mask = mask.at(&(row, col)).is_some();
+                assert_eq!(masked.at(&(row, col)).is_some(), in_mask);
+                assert_eq!(complement.at(&(row, col)).is_some(), !in_mask);
+            }
+        }
+        assert_eq!(&masked + &complement, full);
+
+        let masked_csc = super::csc_mul_csc_masked(&a.to_csc(), &b.to_csc(),
+                                                   &mask.to_csc(),
+                                                   MaskKind::Structure)
+            .unwrap();
+        assert_eq!(masked_csc, masked.to_csc());
+
+        let err = super::csr_mul_csr_masked(&a, &b, &mat3(),
+                                            MaskKind::Structure);
+        assert_eq!(err, Err(SprsError::IncompatibleDimensions));
+        let err = super::csr_mul_csr_masked(&a, &b, &mask.to_csc(),
+                                            MaskKind::Structure);
+        assert_eq!(err, Err(SprsError::BadStorageType));
+
+        let x = vec![1., 2., 3., 4., 5.];
+        let mut expected_output = vec![0.; 5];
+        mul_acc_mat_vec_csr(a.borrowed(), &x, &mut expected_output).unwrap();
+        let rows = CsVec::new_owned(5, vec![1, 3], vec![(); 2]).unwrap();
+        let mut res = vec![-1.; 5];
+        super::mul_acc_mat_vec_csr_masked(a.borrowed(), &x, &mut res,
+                                          rows.borrowed(),
+                                          MaskKind::Structure).unwrap();
+        assert_eq!(res, vec![-1., expected_output[1] - 1., -1.,
+                             expected_output[3] - 1., -1.]);
+        let mut res = vec![0.; 5];
+        super::mul_acc_mat_vec_csr_masked(a.borrowed(), &x, &mut res,
+                                          rows.borrowed(),
+                                          MaskKind::Complement).unwrap();
+        assert_eq!(res, vec![expected_output[0], 0., expected_output[2],
+                             0., expected_output[4]]);
+    }
+
+    /// The pattern of mat3, completed to a square matrix
+    fn mat3_pattern_square() -> CsMatOwned<()> {
+        let mat = mat3();
+        CsMat::new_owned(CSR, 5, 5, mat.indptr().to_vec(),
+                         mat.indices().to_vec(),
+                         vec![(); mat.nb_nonzero()]).unwrap()
+    }
+
+    #[test]
+    fn mul_dense_sparse() {
+        // the transpose of mat_dense2, in both storage orders
+        let a_f = MatOwned::new_owned(mat_dense2().into_data(), 7, 15, [1, 7]);
+        let a_data = a_f.iter_axis(tensor::Axis(0))
+                        .flat_map(|row| row.iter().cloned().collect::<Vec<_>>())
+                        .collect();
+        let a = MatOwned::new_owned(a_data, 7, 15, [15, 1]);
+        let b = mat5().transpose_into();
+        // expected output computed naively from the definition
+        let mut expected_output: MatOwned<f64> = MatOwned::zeros([7, 5]);
+        for i in 0..a.rows() {
+            for j in 0..b.cols() {
+                for k in 0..a.cols() {
+                    let bval = b.at(&(k, j)).unwrap_or(0.);
+                    expected_output[[i, j]] += a[[i, k]] * bval;
+                }
+            }
+        }
+
+        for b in &[b.to_csr(), b.to_csc()] {
+            let mut res: MatOwned<f64> = MatOwned::zeros([a.rows(), b.cols()]);
+            let mut res_f: MatOwned<f64> = MatOwned::zeros_f([a.rows(),
+                                                              b.cols()]);
+            if b.is_csr() {
+                super::dense_rowmaj_mulacc_csr(a.borrowed(), b.borrowed(),
+                                               res.borrowed_mut()).unwrap();
+                super::dense_colmaj_mulacc_csr(a_f.borrowed(), b.borrowed(),
+                                               res_f.borrowed_mut()).unwrap();
+            }
+            else {
+                super::dense_rowmaj_mulacc_csc(a.borrowed(), b.borrowed(),
+                                               res.borrowed_mut()).unwrap();
+                super::dense_colmaj_mulacc_csc(a_f.borrowed(), b.borrowed(),
+                                               res_f.borrowed_mut()).unwrap();
+            }
+            for i in 0..a.rows() {
+                for j in 0..b.cols() {
+                    let expected: f64 = expected_output[[i, j]];
+                    assert!((res[[i, j]] - expected).abs() < 1e-10);
+                    assert!((res_f[[i, j]] - expected).abs() < 1e-10);
+                }
+            }
+            assert_eq!(&a * b, res);
+            assert_eq!(&a_f * b, res_f);
+        }
+
+        let mut res = MatOwned::zeros([a.rows(), a.rows()]);
+        let err = super::dense_rowmaj_mulacc_csr(a.borrowed(),
+                                                 mat1().borrowed(),
+                                                 res.borrowed_mut());
+        assert_eq!(err, Err(SprsError::IncompatibleDimensions));
+        let mut res = MatOwned::zeros([a.rows(), b.cols()]);
+        let err = super::dense_rowmaj_mulacc_csr(a.borrowed(), b.borrowed(),
+                                                 res.borrowed_mut());
+        assert_eq!(err, Err(SprsError::BadStorageType));
+    }
+
+    #[test]
+    fn triple_products() {
+        let a = mat1();
+        let p = CsMat::new_owned(CSR, 5, 3, vec![0, 1, 3, 4, 5, 6],
+                                 vec![0, 0, 1, 1, 2, 2],
+                                 vec![1., 2., 1., 3., 1., 2.]).unwrap();
+        let expected_output = (&(&p.transpose_view() * &a) * &p).to_csr();
+        let res = super::galerkin_product(&a, &p).unwrap();
+        assert_eq!(res, expected_output);
+
+        // both storages of r give the same result
+        let r = p.transpose_view().to_other_storage();
+        assert!(r.is_csr());
+        let res = super::rap(&r, &a, &p).unwrap();
+        assert_eq!(res, expected_output);
+
+        // numeric refresh for new values of a
+        let a2 = &a * 3.;
+        let expected_output = (&(&p.transpose_view() * &a2) * &p).to_csr();
+        for r in &[p.transpose_view().to_owned(), r] {
+            let mut res = super::rap_symbolic(r, &a, &p).unwrap();
+            let mut spa = SparseAccumulator::new(p.cols());
+            super::rap_numeric(r, &a2, &p, &mut res, &mut spa).unwrap();
+            assert_eq!(res, expected_output);
+
+            // the pattern obtained for a sparser a does not fit
+            let eye = CsMat::eye(CSR, 5);
+            let mut res = super::rap_symbolic(r, &eye, &p).unwrap();
+            let err = super::rap_numeric(r, &a, &p, &mut res, &mut spa);
+            assert_eq!(err, Err(SprsError::IncompatiblePatterns));
+
+            // the accumulator is still usable after an error
+            let mut res = super::rap_symbolic(r, &a, &p).unwrap();
+            super::rap_numeric(r, &a2, &p, &mut res, &mut spa).unwrap();
+            assert_eq!(res, expected_output);
+        }
+
+        let err = super::galerkin_product(&a, &p.to_csc());
+        assert_eq!(err, Err(SprsError::BadStorageType));
+        let err = super::rap(&p, &a, &p);
+        assert_eq!(err, Err(SprsError::IncompatibleDimensions));
+    }
+
+    #[test]
+    fn mul_csc_csvec_accumulator() {
+        let a = mat1_csc();
+        let x = CsVec::new_owned(5, vec![0, 2, 4], vec![1.; 3]).unwrap();
+        let expected_output = csr_mul_csvec(mat1().borrowed(), x.borrowed())
+            .unwrap();
+        let res = super::csc_mul_csvec(a.borrowed(), x.borrowed()).unwrap();
+        assert_eq!(res, expected_output);
+        assert_eq!(&a * &x, expected_output);
+
+        // cancellations are removed, except for semiring products
+        let a = CsMat::new_owned(CSC, 3, 2, vec![0, 2, 3], vec![0, 2, 0],
+                                 vec![1., 1., -1.]).unwrap();
+        let x = CsVec::new_owned(2, vec![0, 1], vec![1., 1.]).unwrap();
+        let res = super::csc_mul_csvec(a.borrowed(), x.borrowed()).unwrap();
+        assert_eq!(res.indices(), &[2]);
+        let res = super::csc_mul_csvec_semiring(&PlusTimes, a.borrowed(),
+                                                x.borrowed()).unwrap();
+        assert_eq!(res.indices(), &[0, 2]);
+        assert_eq!(res.data(), &[0., 1.]);
+
+        // breadth first search steps in 0 -> 1, 0 -> 2, 2 -> 3
+        let graph = CsMat::new_owned(CSC, 4, 4, vec![0, 2, 2, 3, 3],
+                                     vec![1, 2, 3], vec![true; 3]).unwrap();
+        let frontier = CsVec::new_owned(4, vec![0], vec![true]).unwrap();
+        let next = super::csc_mul_csvec_semiring(&OrAnd, graph.borrowed(),
+                                                 frontier.borrowed()).unwrap();
+        assert_eq!(next.indices(), &[1, 2]);
+        let next = super::csc_mul_csvec_semiring(&OrAnd, graph.borrowed(),
+                                                 next.borrowed()).unwrap();
+        assert_eq!(next.indices(), &[3]);
+
+        let res = super::csc_mul_csvec(mat1().borrowed(), x.borrowed());
+        assert_eq!(res, Err(SprsError::IncompatibleDimensions));
+        let x = CsVec::new_owned(5, vec![0], vec![1.]).unwrap();
+        let res = super::csc_mul_csvec(mat1().borrowed(), x.borrowed());
+        assert_eq!(res, Err(SprsError::BadStorageType));
+    }
+
+    #[test]
+    fn mul_csc_csvec_reused_accumulator() {
+        use sparse::accumulator::SparseAccumulator;
+        let a = mat1_csc();
+        let mut spa = SparseAccumulator::new(5);
+        // dirty the accumulator, it should be reset by the products
+        spa.accumulate(3, 7.);
+        for col in 0..4 {
+            let x = CsVec::new_owned(5, vec![col, 4], vec![1., 2.]).unwrap();
+            let expected_output = csr_mul_csvec(mat1().borrowed(),
+                                                x.borrowed()).unwrap();
+            let res = super::csc_mul_csvec_spa(a.borrowed(), x.borrowed(),
+                                               &mut spa).unwrap();
+            assert_eq!(res, expected_output);
+        }
+        let x = CsVec::new_owned(5, vec![0], vec![true]).unwrap();
+        let graph = a.map(|_| true);
+        let mut spa = SparseAccumulator::new(5);
+        let res = super::csc_mul_csvec_semiring_spa(&OrAnd, graph.borrowed(),
+                                                    x.borrowed(), &mut spa)
+            .unwrap();
+        assert_eq!(res.indices(), a.outer_view(0).unwrap().indices());
+
+        let mut spa = SparseAccumulator::new(4);
+        let x = CsVec::new_owned(5, vec![0], vec![1.]).unwrap();
+        let res = super::csc_mul_csvec_spa(a.borrowed(), x.borrowed(),
+                                           &mut spa);
+        assert_eq!(res, Err(SprsError::BadWorkspaceDimensions));
+    }
+
+    #[test]
+    fn blas_like_products() {
+        use super::Transpose;
+        let a = mat1();
+        let x: Vec<f64> = vec![0.1, 0.2, -0.1, 0.1, 0.1];
+        let y0 = vec![1., 2., 3., 4., 5.];
+        let mut expected_output: Vec<f64> = vec![0.; 5];
+        mul_acc_mat_vec_csr(a.borrowed(), &x, &mut expected_output).unwrap();
+        for (i, val) in expected_output.iter_mut().enumerate() {
+            *val = 2. * *val - y0[i];
+        }
+        for mat in &[a.to_owned(), a.to_csc()] {
+            let mut y = y0.clone();
+            super::spmv(2., mat, Transpose::No, &x, -1., &mut y).unwrap();
+            for (res, exp) in y.iter().zip(expected_output.iter()) {
+                assert!((res - exp).abs() < 1e-12);
+            }
+            let mut y = y0.clone();
+            let at = mat.transpose_view().to_other_storage();
+            super::spmv(2., &at, Transpose::Yes, &x, -1., &mut y).unwrap();
+            for (res, exp) in y.iter().zip(expected_output.iter()) {
+                assert!((res - exp).abs() < 1e-12);
+            }
+        }
+
+        // beta == 0 ignores the content of y
+        let mut y = vec![f64::NAN; 5];
+        super::spmv(1., &a, Transpose::No, &x, 0., &mut y).unwrap();
+        assert!(y.iter().all(|val| val.is_finite()));
+
+        let mut y = vec![0.; 4];
+        let res = super::spmv(1., &a, Transpose::No, &x, 0., &mut y);
+        assert_eq!(res, Err(SprsError::IncompatibleDimensions));
+
+        // c = 2 a^T b - c, for all orderings of b and c
+        let b_data: Vec<f64> = (0..15).map(|i| i as f64).collect();
+        let b_c = MatOwned::new_owned(b_data, 5, 3, [3, 1]);
+        let b_f_data = (0..15).map(|k| ((k % 5) * 3 + k / 5) as f64).collect();
+        let b_f = MatOwned::new_owned(b_f_data, 5, 3, [1, 5]);
+        let c_c = MatOwned::new_owned(vec![1.; 15], 5, 3, [3, 1]);
+        let c_f = MatOwned::new_owned(vec![1.; 15], 5, 3, [1, 5]);
+        let at = a.transpose_view().to_csr();
+        let mut expected_output = c_c.to_owned();
+        super::csr_mulacc_dense_rowmaj(at.borrowed(), b_c.borrowed(),
+                                       expected_output.borrowed_mut())
+            .unwrap();
+        for b in &[&b_c, &b_f] {
+            for c in &[&c_c, &c_f] {
+                for mat in &[a.to_owned(), a.to_csc()] {
+                    let mut res = c.borrowed().to_owned();
+                    super::spmm(2., mat, Transpose::Yes, b.borrowed(), -1.,
+                                res.borrowed_mut()).unwrap();
+                    for i in 0..5 {
+                        for j in 0..3 {
+                            let exp = 2. * expected_output[[i, j]] - 3.;
+                            assert!((res[[i, j]] - exp).abs() < 1e-12);
+                        }
+                    }
+                }
+            }
+        }
+        let mut res = c_c.to_owned();
+        let err = super::spmm(1., &mat3(), Transpose::No, b_c.borrowed(), 1.,
+                              res.borrowed_mut());
+        assert_eq!(err, Err(SprsError::IncompatibleDimensions));
+    }
+
+    #[test]
+    fn gram_matrices() {
+        use super::Triangle;
+        use sparse::symmetric::is_symmetric;
+        use sparse::linalg::cholesky::LdlSymbolic;
+        let a = mat1();
+        let expected_output = &a.transpose_view() * &a;
+        let res = super::gram(&a, Triangle::Full);
+        assert_eq!(res, expected_output);
+        assert!(is_symmetric(&res));
+        assert!(LdlSymbolic::new(&res).is_ok());
+        assert_eq!(super::gram(&a.to_csc(), Triangle::Full), expected_output);
+
+        let upper = super::gram(&a, Triangle::Upper);
+        assert!(upper.is_csc());
+        for (col, vec) in upper.outer_iterator() {
+            assert!(vec.indices().iter().all(|&row| row <= col));
+            for (row, val) in vec.iter() {
+                assert_eq!(Some(val), expected_output.at(&(row, col)));
+            }
+        }
+        assert_eq!(upper.nb_nonzero(),
+                   (expected_output.nb_nonzero() + a.cols()) / 2);
+
+        // values whose products round differently depending on the order
+        // of the summation still give an exactly symmetric result
+        let c: CsMatOwned<f64> =
+            CsMat::new_owned(CSR, 3, 3, vec![0, 3, 5, 7],
+                             vec![0, 1, 2, 0, 2, 1, 2],
+                             vec![0.1, 0.7, 1.3, 0.3, 2.9, 0.55, 1.1])
+                .unwrap();
+        let res = super::gram(&c, Triangle::Full);
+        assert!(is_symmetric(&res));
+        let expected_output = &c.transpose_view() * &c;
+        assert_eq!(res.indptr(), expected_output.indptr());
+        assert_eq!(res.indices(), expected_output.indices());
+        for (val, expected) in res.data().iter()
+                                  .zip(expected_output.data().iter()) {
+            assert!((val - expected).abs() < 1e-12);
+        }
+
+        // a * a^T, with an empty column in the result
+        let b = mat3();
+        let expected_output = (&b * &b.transpose_view()).to_csc();
+        let res = super::gram(&b.transpose_view(), Triangle::Full);
+        assert_eq!(res, expected_output);
+
+        let b_t = b.transpose_view();
+        let index = super::other_storage_index(&b_t);
+        for &triangle in &[Triangle::Full, Triangle::Upper] {
+            let res = super::gram_indexed(&b_t, &index, triangle).unwrap();
+            assert_eq!(res, super::gram(&b_t, triangle));
+        }
+        let res = super::gram_indexed(&b_t, &index.to_other_storage(),
+                                      Triangle::Full);
+        assert_eq!(res, Err(SprsError::IncompatibleStorages));
+        let res = super::gram_indexed(&b, &index, Triangle::Full);
+        assert_eq!(res, Err(SprsError::IncompatibleDimensions));
+    }
+
     #[test]
     fn mul_csr_csvec() {
         let a = mat1();
@@ -590,4 +3169,88 @@ mod test {
         let c = &a * &b;
         assert_eq!(c, expected_output);
     }
+
+    /// A pseudo random matrix with floating point values, so that the
+    /// products are sensitive to the order of accumulation
+    #[cfg(feature = "rayon")]
+    fn pseudo_random_csr(rows: usize, cols: usize, seed: u64) -> CsMatOwned<f64> {
+        let mut state = seed;
+        let mut next = move || {
+            state = state.wrapping_mul(6364136223846793005)
+                         .wrapping_add(1442695040888963407);
+            state >> 33
+        };
+        let mut indptr = vec![0];
+        let mut indices = Vec::new();
+        let mut data = Vec::new();
+        for _ in 0..rows {
+            // rows of very different densities to exercise the balancing
+            let density = 1 + next() % 16;
+            for col in 0..cols {
+                if next() % 64 < density {
+                    indices.push(col);
+                    data.push((next() % 1000) as f64 / 7.);
+                }
+            }
+            indptr.push(indices.len());
+        }
+        CsMat::new_owned(CSR, rows, cols, indptr, indices, data).unwrap()
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_products_match_sequential() {
+        let a = pseudo_random_csr(301, 203, 1);
+        let b = pseudo_random_csr(203, 250, 2);
+
+        let x: Vec<f64> = (0..203).map(|i| 1. / (i + 1) as f64).collect();
+        let mut y = vec![0.5; 301];
+        let mut par_y = y.clone();
+        mul_acc_mat_vec_csr(a.borrowed(), &x, &mut y).unwrap();
+        super::par_mul_acc_mat_vec_csr(a.borrowed(), &x, &mut par_y).unwrap();
+        assert_eq!(y, par_y);
+
+        let mut spa = SparseAccumulator::new(b.cols());
+        let c = super::csr_mul_csr_spa(&a, &b, &mut spa).unwrap();
+        let par_c = super::par_csr_mul_csr(&a, &b).unwrap();
+        assert_eq!(c, par_c);
+
+        let dense_b: Vec<f64> = (0..203 * 7).map(|i| 1. / (i + 3) as f64)
+                                            .collect();
+        let rhs_c = MatOwned::new_owned(dense_b.clone(), 203, 7, [7, 1]);
+        let rhs_f = MatOwned::new_owned(dense_b, 203, 7, [1, 203]);
+        let out_c = MatOwned::new_owned(vec![0.25; 301 * 7], 301, 7, [7, 1]);
+        let out_f = MatOwned::new_owned(vec![0.25; 301 * 7], 301, 7, [1, 301]);
+
+        let mut res = out_c.to_owned();
+        let mut par_res = out_c.to_owned();
+        csr_mulacc_dense_rowmaj(a.borrowed(), rhs_c.borrowed(),
+                                res.borrowed_mut()).unwrap();
+        super::par_csr_mulacc_dense_rowmaj(a.borrowed(), rhs_c.borrowed(),
+                                           par_res.borrowed_mut()).unwrap();
+        assert_eq!(res, par_res);
+
+        // the parallel split is done by rows for rowmaj outputs,
+        // and by columns for colmaj outputs
+        for out in &[out_c, out_f] {
+            let mut res = out.to_owned();
+            let mut par_res = out.to_owned();
+            super::csr_mulacc_dense_colmaj(a.borrowed(), rhs_f.borrowed(),
+                                           res.borrowed_mut()).unwrap();
+            super::par_csr_mulacc_dense_colmaj(a.borrowed(), rhs_f.borrowed(),
+                                               par_res.borrowed_mut())
+                .unwrap();
+            assert_eq!(res, par_res);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn nnz_balanced_chunks() {
+        let indptr = [0, 10, 10, 11, 12, 20, 40];
+        let bounds = super::nnz_balanced_chunks(&indptr, 4);
+        assert_eq!(bounds, vec![0, 1, 5, 6]);
+        assert_eq!(super::nnz_balanced_chunks(&[3, 3, 3], 4), vec![0, 2]);
+        assert_eq!(super::nnz_balanced_chunks(&[0], 4), vec![0]);
+    }
 }
@@ -0,0 +1,79 @@
+//! Semirings for generalized sparse matrix products
+//!
+//! A semiring replaces the addition and multiplication used by matrix
+//! products. The additive identity plays the role of the implicit value
+//! of the entries absent from a sparse matrix.
+
+use num::traits::{Num, Float};
+
+/// A semiring over the scalar type N
+///
+/// Implementors should make `add` associative and commutative with
+/// `zero` as its identity, and `mul` associative and distributive over
+/// `add`.
+pub trait Semiring<N> {
+    /// The additive identity
+    fn zero(&self) -> N;
+
+    /// The addition of the semiring
+    fn add(&self, lhs: N, rhs: N) -> N;
+
+    /// The multiplication of the semiring
+    fn mul(&self, lhs: N, rhs: N) -> N;
+}
+
+/// The usual (+, *) semiring
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PlusTimes;
+
+impl<N: Num + Copy> Semiring<N> for PlusTimes {
+    fn zero(&self) -> N {
+        N::zero()
+    }
+
+    fn add(&self, lhs: N, rhs: N) -> N {
+        lhs + rhs
+    }
+
+    fn mul(&self, lhs: N, rhs: N) -> N {
+        lhs * rhs
+    }
+}
+
+/// The tropical (min, +) semiring, whose products compute shortest
+/// path steps in a graph weighted by the matrix values
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MinPlus;
+
+impl<N: Float> Semiring<N> for MinPlus {
+    fn zero(&self) -> N {
+        N::infinity()
+    }
+
+    fn add(&self, lhs: N, rhs: N) -> N {
+        lhs.min(rhs)
+    }
+
+    fn mul(&self, lhs: N, rhs: N) -> N {
+        lhs + rhs
+    }
+}
+
+/// The boolean (or, and) semiring, whose products compute reachability
+/// in a graph
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct OrAnd;
+
+impl Semiring<bool> for OrAnd {
+    fn zero(&self) -> bool {
+        false
+    }
+
+    fn add(&self, lhs: bool, rhs: bool) -> bool {
+        lhs || rhs
+    }
+
+    fn mul(&self, lhs: bool, rhs: bool) -> bool {
+        lhs && rhs
+    }
+}
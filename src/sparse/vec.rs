@@ -16,21 +16,24 @@
 /// assert_eq!(iter.next(), None);
 /// ```
 
-use std::iter::{Zip, Peekable, FilterMap, IntoIterator, Enumerate};
-use std::ops::{Deref, Mul, Add, Sub};
+use std::iter::{Zip, Peekable, FilterMap, IntoIterator, Enumerate,
+                FromIterator};
+use std::ops::{Deref, DerefMut, Mul, Add, Sub, Neg};
 use std::convert::AsRef;
 use std::cmp;
 use std::slice::{self, Iter};
 use std::collections::HashSet;
 use std::hash::Hash;
 
-use num::traits::Num;
+use num::traits::{Num, Signed, Float};
 
 use sparse::permutation::PermView;
 use sparse::{prod, binop};
+use sparse::accumulator::SparseAccumulator;
 use sparse::csmat::{CsMat, CsMatVecView};
 use sparse::csmat::CompressedStorage::{CSR, CSC};
-use errors::SprsError;
+use errors::{SprsError, StructureError};
+use utils;
 
 /// A sparse vector, storing the indices of its non-zero data.
 /// The indices should be sorted.
@@ -267,6 +270,23 @@ impl<'a, N: 'a + Copy> CsVec<N, &'a[usize], &'a[N]> {
         v.check_structure().and(Ok(v))
     }
 
+    /// Create a borrowed CsVec over slice data, checking its structure as
+    /// `new_borrowed` does, but reporting a failed check as
+    /// `SprsError::Structure`, which locates the offending index.
+    pub fn new_borrowed_detailed(
+        n: usize,
+        indices: &'a [usize],
+        data: &'a [N])
+    -> Result<CsVec<N, &'a[usize], &'a[N]>, SprsError> {
+        let v = CsVec {
+            dim: n,
+            indices,
+            data,
+        };
+        v.check_structure_detailed()?;
+        Ok(v)
+    }
+
     /// Create a borrowed CsVec over slice data without checking the structure
     /// This is unsafe because algorithms are free to assume
     /// that properties guaranteed by check_structure are enforced.
@@ -299,6 +319,56 @@ impl<N: Copy> CsVec<N, Vec<usize>, Vec<N>> {
         v.check_structure().and(Ok(v))
     }
 
+    /// Create an owning CsVec from vector data, checking its structure as
+    /// `new_owned` does, but reporting a failed check as
+    /// `SprsError::Structure`, which locates the offending index.
+    pub fn new_owned_detailed(n: usize,
+                              indices: Vec<usize>,
+                              data: Vec<N>
+                             ) -> Result<CsVec<N, Vec<usize>, Vec<N>>,
+                                         SprsError> {
+        let v = CsVec {
+            dim: n,
+            indices,
+            data
+        };
+        v.check_structure_detailed()?;
+        Ok(v)
+    }
+
+    /// Create an owning CsVec from vector data, sorting the indices
+    /// and merging duplicate entries.
+    ///
+    /// The values of duplicate entries are merged using `combine`, in their
+    /// order of appearance in `data`. The resulting structure is then checked
+    /// as in `new_owned`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use sprs::CsVec;
+    /// let v = CsVec::new_owned_unsorted(5, vec![3, 0, 3], vec![1., 2., 3.],
+    ///                                   |x, y| x + y).unwrap();
+    /// assert_eq!(v.indices(), &[0, 3]);
+    /// assert_eq!(v.data(), &[2., 4.]);
+    /// ```
+    pub fn new_owned_unsorted<F>(n: usize,
+                                 indices: Vec<usize>,
+                                 data: Vec<N>,
+                                 combine: F
+                                ) -> Result<CsVec<N, Vec<usize>, Vec<N>>,
+                                            SprsError>
+    where F: Fn(N, N) -> N {
+        if indices.len() != data.len() {
+            return Err(SprsError::DataIndicesMismatch);
+        }
+        let mut out_indices = Vec::with_capacity(indices.len());
+        let mut out_data = Vec::with_capacity(data.len());
+        utils::sort_and_merge_slice(&indices, &data, &combine,
+                                    &mut out_indices, &mut out_data);
+        CsVec::new_owned(n, out_indices, out_data)
+    }
+
     /// Create an empty CsVec, which can be used for incremental construction
     pub fn empty(dim: usize) -> CsVec<N, Vec<usize>, Vec<N>> {
         CsVec {
@@ -345,6 +415,157 @@ impl<N: Copy> CsVec<N, Vec<usize>, Vec<N>> {
         self.indices.clear();
         self.data.clear();
     }
+
+    /// Create an owning CsVec of dimension n from an iterator of
+    /// (index, value) pairs, in any order. For duplicate indices, the
+    /// last value is kept.
+    ///
+    /// # Errors
+    ///
+    /// - `OutOfBoundsIndex` if an index is not lower than n
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use sprs::CsVec;
+    /// let v = CsVec::new_from_iter(5, vec![(3, 1.), (0, 2.), (3, 4.)])
+    ///               .unwrap();
+    /// assert_eq!(v.indices(), &[0, 3]);
+    /// assert_eq!(v.data(), &[2., 4.]);
+    /// ```
+    pub fn new_from_iter<I>(n: usize, iter: I)
+                            -> Result<CsVec<N, Vec<usize>, Vec<N>>, SprsError>
+    where I: IntoIterator<Item=(usize, N)> {
+        let (indices, data): (Vec<_>, Vec<_>) = iter.into_iter().unzip();
+        CsVec::new_owned_unsorted(n, indices, data, |_, y| y)
+    }
+
+    /// Insert an element in the vector, keeping the indices sorted.
+    /// Returns the previous value if the element was already present.
+    ///
+    /// This has a linear complexity in the number of non zeros, prefer
+    /// `append` or `extend` to insert many elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ind` is not lower than `self.dim()`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use sprs::CsVec;
+    /// let mut v = CsVec::new_owned(5, vec![1, 3], vec![1., 3.]).unwrap();
+    /// assert_eq!(v.insert(2, 2.), None);
+    /// assert_eq!(v.insert(3, 4.), Some(3.));
+    /// assert_eq!(v.indices(), &[1, 2, 3]);
+    /// assert_eq!(v.data(), &[1., 2., 4.]);
+    /// ```
+    pub fn insert(&mut self, ind: usize, val: N) -> Option<N> {
+        assert!(ind < self.dim, "out of bounds index");
+        match self.indices.binary_search(&ind) {
+            Ok(position) => {
+                let prev = self.data[position];
+                self.data[position] = val;
+                Some(prev)
+            }
+            Err(position) => {
+                self.indices.insert(position, ind);
+                self.data.insert(position, val);
+                None
+            }
+        }
+    }
+
+    /// Remove an element from the vector, returning its value if it
+    /// was present.
+    pub fn remove(&mut self, ind: usize) -> Option<N> {
+        match self.indices.binary_search(&ind) {
+            Ok(position) => {
+                self.indices.remove(position);
+                Some(self.data.remove(position))
+            }
+            Err(_) => None,
+        }
+    }
+
+    /// Only keep the elements for which f returns true, given their
+    /// index and value.
+    pub fn retain<F>(&mut self, mut f: F)
+    where F: FnMut(usize, N) -> bool {
+        let mut nnz = 0;
+        for position in 0..self.indices.len() {
+            let ind = self.indices[position];
+            let val = self.data[position];
+            if f(ind, val) {
+                self.indices[nnz] = ind;
+                self.data[nnz] = val;
+                nnz += 1;
+            }
+        }
+        self.indices.truncate(nnz);
+        self.data.truncate(nnz);
+    }
+
+    /// Add the (index, value) pairs of iter to the vector, in any order.
+    ///
+    /// The values of duplicate entries, including those already present
+    /// in the vector, are merged using `combine` in their order of
+    /// appearance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an index is not lower than `self.dim()`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use sprs::CsVec;
+    /// let mut v = CsVec::new_owned(5, vec![1, 3], vec![1., 3.]).unwrap();
+    /// v.extend_combine(vec![(4, 1.), (1, 1.), (4, 2.)], |x, y| x + y);
+    /// assert_eq!(v.indices(), &[1, 3, 4]);
+    /// assert_eq!(v.data(), &[2., 3., 3.]);
+    /// ```
+    pub fn extend_combine<I, F>(&mut self, iter: I, combine: F)
+    where I: IntoIterator<Item=(usize, N)>,
+          F: Fn(N, N) -> N {
+        let mut indices = self.indices.clone();
+        let mut data = self.data.clone();
+        for (ind, val) in iter {
+            assert!(ind < self.dim, "out of bounds index");
+            indices.push(ind);
+            data.push(val);
+        }
+        self.clear();
+        utils::sort_and_merge_slice(&indices, &data, &combine,
+                                    &mut self.indices, &mut self.data);
+    }
+}
+
+impl<N: Copy> Extend<(usize, N)> for CsVec<N, Vec<usize>, Vec<N>> {
+    /// Add (index, value) pairs to the vector, in any order. For
+    /// duplicate indices, the last value is kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an index is not lower than `self.dim()`
+    fn extend<I>(&mut self, iter: I)
+    where I: IntoIterator<Item=(usize, N)> {
+        self.extend_combine(iter, |_, y| y);
+    }
+}
+
+impl<N: Copy> FromIterator<(usize, N)> for CsVec<N, Vec<usize>, Vec<N>> {
+    /// Collect (index, value) pairs into a vector whose dimension is one
+    /// more than the largest index. For duplicate indices, the last value
+    /// is kept. Use `CsVec::new_from_iter` to give the dimension
+    /// explicitly.
+    fn from_iter<I>(iter: I) -> Self
+    where I: IntoIterator<Item=(usize, N)> {
+        let (indices, data): (Vec<_>, Vec<_>) = iter.into_iter().unzip();
+        let dim = indices.iter().max().map_or(0, |&ind| ind + 1);
+        CsVec::new_owned_unsorted(dim, indices, data, |_, y| y)
+            .expect("dimension covers all indices")
+    }
 }
 
 impl<N, IStorage, DStorage> CsVec<N, IStorage, DStorage>
@@ -362,6 +583,46 @@ DStorage: Deref<Target=[N]> {
     }
 }
 
+impl<N, IStorage, DStorage> CsVec<N, IStorage, DStorage>
+where N: Copy,
+IStorage: Deref<Target=[usize]>,
+DStorage: DerefMut<Target=[N]> {
+
+    /// Mutable access to the non zero values
+    pub fn data_mut(&mut self) -> &mut [N] {
+        &mut self.data[..]
+    }
+
+    /// Multiply in place each value by val
+    pub fn scale(&mut self, val: N)
+    where N: Num {
+        for data in self.data_mut() {
+            *data = *data * val;
+        }
+    }
+
+    /// Scale the vector so that its euclidean norm is one. A vector whose
+    /// norm is zero is left untouched.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use sprs::CsVec;
+    /// let mut v = CsVec::new_owned(4, vec![1, 3], vec![3., -4.]).unwrap();
+    /// v.normalize();
+    /// assert_eq!(v.data(), &[0.6, -0.8]);
+    /// ```
+    pub fn normalize(&mut self)
+    where N: Float {
+        let norm = self.l2_norm();
+        if norm > N::zero() {
+            for data in self.data_mut() {
+                *data = *data / norm;
+            }
+        }
+    }
+}
+
 impl<'a, N, IStorage, DStorage> CsVec<N, IStorage, DStorage>
 where N: 'a + Copy,
 IStorage: 'a + Deref<Target=[usize]>,
@@ -421,15 +682,13 @@ DStorage: Deref<Target=[N]> {
     /// - indices is sorted
     /// - indices are lower than dims()
     pub fn check_structure(&self) -> Result<(), SprsError> {
-        if ! self.indices.windows(2).all(|x| x[0] < x[1]) {
-            return Err(SprsError::NonSortedIndices);
-        }
-
-        if self.indices.iter().max().unwrap_or(&0) >= &self.dim {
-            return Err(SprsError::OutOfBoundsIndex);
-        }
+        self.check_structure_detailed().map_err(|e| e.kind())
+    }
 
-        Ok(())
+    /// Check the sparse structure, as `check_structure` does, but
+    /// describe the location of the first problem found.
+    pub fn check_structure_detailed(&self) -> Result<(), StructureError> {
+        utils::check_sparse_indices(&self.indices, self.dim, None, 0)
     }
 
     /// Allocate a new vector equal to this one.
@@ -497,6 +756,60 @@ DStorage: Deref<Target=[N]> {
                                        .fold(N::zero(), |x, y| x + y)
     }
 
+    /// Dot product with a dense vector
+    ///
+    /// # Panics
+    ///
+    /// If the length of rhs differs from the dimension of the vector
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use sprs::CsVec;
+    /// let v = CsVec::new_owned(4, vec![1, 3], vec![2., -1.]).unwrap();
+    /// assert_eq!(v.dot_dense(&[1., 2., 3., 4.]), 0.);
+    /// ```
+    pub fn dot_dense(&self, rhs: &[N]) -> N
+    where N: Num {
+        assert_eq!(self.dim, rhs.len(), "dimension mismatch");
+        self.iter().fold(N::zero(), |acc, (ind, val)| acc + val * rhs[ind])
+    }
+
+    /// Add alpha times this vector to the dense vector y
+    ///
+    /// # Panics
+    ///
+    /// If the length of y differs from the dimension of the vector
+    pub fn axpy(&self, alpha: N, y: &mut [N])
+    where N: Num {
+        assert_eq!(self.dim, y.len(), "dimension mismatch");
+        for (ind, val) in self.iter() {
+            y[ind] = y[ind] + alpha * val;
+        }
+    }
+
+    /// Sum of the absolute values of the non zero elements
+    pub fn l1_norm(&self) -> N
+    where N: Signed {
+        self.data.iter().fold(N::zero(), |acc, val| acc + val.abs())
+    }
+
+    /// Euclidean norm of the vector
+    pub fn l2_norm(&self) -> N
+    where N: Float {
+        self.data.iter().fold(N::zero(), |acc, &val| acc + val * val).sqrt()
+    }
+
+    /// Largest absolute value of the non zero elements, or zero for an
+    /// empty vector
+    pub fn linf_norm(&self) -> N
+    where N: Signed + PartialOrd {
+        self.data.iter().fold(N::zero(), |acc, val| {
+            let val = val.abs();
+            if val > acc { val } else { acc }
+        })
+    }
+
     /// Fill a dense vector with our values
     pub fn scatter(&self, out: &mut [N]) {
         for (ind, val) in self.iter() {
@@ -543,7 +856,9 @@ where N: Copy + Num + Default,
             prod::csr_mul_csvec(self.borrowed(), rhs.borrowed()).unwrap()
         }
         else {
-            (self * &rhs.col_view()).outer_view(0).unwrap().to_owned()
+            let mut spa = SparseAccumulator::new(self.rows());
+            prod::csc_mul_csvec_spa(self.borrowed(), rhs.borrowed(), &mut spa)
+                .unwrap()
         }
     }
 }
@@ -580,11 +895,44 @@ where N: Copy + Num,
     }
 }
 
+impl<N, IStorage, DStorage> Mul<N>
+for &CsVec<N, IStorage, DStorage>
+where N: Copy + Num,
+      IStorage: Deref<Target=[usize]>,
+      DStorage: Deref<Target=[N]> {
+
+    type Output = CsVecOwned<N>;
+
+    fn mul(self, rhs: N) -> CsVecOwned<N> {
+        let mut res = self.to_owned();
+        res.scale(rhs);
+        res
+    }
+}
+
+impl<N, IStorage, DStorage> Neg
+for &CsVec<N, IStorage, DStorage>
+where N: Copy + Neg<Output=N>,
+      IStorage: Deref<Target=[usize]>,
+      DStorage: Deref<Target=[N]> {
+
+    type Output = CsVecOwned<N>;
+
+    fn neg(self) -> CsVecOwned<N> {
+        CsVec {
+            dim: self.dim,
+            indices: self.indices.to_vec(),
+            data: self.data.iter().map(|&val| -val).collect(),
+        }
+    }
+}
+
 
 #[cfg(test)]
 mod test {
     use super::CsVec;
     use super::SparseIterTools;
+    use errors::SprsError;
 
     fn test_vec1() -> CsVec<f64, Vec<usize>, Vec<f64>> {
         let n = 8;
@@ -640,4 +988,119 @@ mod test {
         assert_eq!(6., vec1.dot(&vec3));
         assert_eq!(12., vec2.dot(&vec3));
     }
+
+    #[test]
+    fn new_owned_unsorted() {
+        let v = CsVec::new_owned_unsorted(8,
+                                          vec![5, 1, 7, 1, 0, 5],
+                                          vec![1., 2., 3., 4., 5., 6.],
+                                          |x, y| x + y).unwrap();
+        let expected = CsVec::new_owned(8,
+                                        vec![0, 1, 5, 7],
+                                        vec![5., 6., 7., 3.]).unwrap();
+        assert_eq!(v, expected);
+
+        // duplicates are combined in their order of appearance
+        let v = CsVec::new_owned_unsorted(4,
+                                          vec![2, 2, 2],
+                                          vec![1., 2., 3.],
+                                          |_, y| y).unwrap();
+        assert_eq!(v.data(), &[3.]);
+
+        let res = CsVec::new_owned_unsorted(4, vec![4, 2], vec![1., 2.],
+                                            |x, y| x + y);
+        assert_eq!(res, Err(SprsError::OutOfBoundsIndex));
+        let res = CsVec::new_owned_unsorted(4, vec![1, 2], vec![1.],
+                                            |x, y| x + y);
+        assert_eq!(res, Err(SprsError::DataIndicesMismatch));
+    }
+
+    #[test]
+    fn vector_algebra() {
+        // test_vec1 has values 0, 1, 4, 5, 7 at indices 0, 1, 4, 5, 7
+        let v = test_vec1();
+        let dense: Vec<f64> = (0..8).map(|i| i as f64).collect();
+        assert_eq!(v.dot_dense(&dense), 1. + 16. + 25. + 49.);
+        let mut y = vec![1.; 8];
+        v.axpy(2., &mut y);
+        assert_eq!(y, vec![1., 3., 1., 1., 9., 11., 1., 15.]);
+
+        let w = &v * -2.;
+        assert_eq!(w.indices(), v.indices());
+        assert_eq!(w.data(), &[-0., -2., -8., -10., -14.]);
+        assert_eq!(-&w, &v * 2.);
+        assert_eq!(w.l1_norm(), 34.);
+        assert_eq!(w.linf_norm(), 14.);
+        assert_eq!(v.l2_norm(), 91f64.sqrt());
+
+        let mut w = w;
+        w.scale(-0.5);
+        assert_eq!(w, v);
+        w.normalize();
+        assert!((w.l2_norm() - 1.).abs() < 1e-12);
+        let mut zero = CsVec::new_owned(3, vec![1], vec![0.]).unwrap();
+        zero.normalize();
+        assert_eq!(zero.data(), &[0.]);
+    }
+
+    #[test]
+    fn new_detailed() {
+        use errors::StructureError;
+        let err = CsVec::new_owned_detailed(5, vec![0, 3, 2], vec![1.; 3]);
+        assert_eq!(err, Err(SprsError::Structure(
+            StructureError::NonSortedIndices {
+                outer: None, position: 2, index: 2, previous: 3 })));
+        // the plain constructors keep returning the plain variants
+        let err = CsVec::new_owned(5, vec![0, 3, 2], vec![1.; 3]);
+        assert_eq!(err, Err(SprsError::NonSortedIndices));
+        let indices = [0, 5];
+        let data = [1., 2.];
+        let err = CsVec::new_borrowed_detailed(5, &indices, &data);
+        assert_eq!(err, Err(SprsError::Structure(
+            StructureError::OutOfBoundsIndex {
+                outer: None, position: 1, index: 5, bound: 5 })));
+        assert!(CsVec::new_borrowed_detailed(6, &indices, &data).is_ok());
+    }
+
+    #[test]
+    fn owned_vector_edition() {
+        let mut v = test_vec1();
+        assert_eq!(v.insert(3, 3.), None);
+        assert_eq!(v.insert(7, 8.), Some(7.));
+        assert_eq!(v.insert(2, 2.), None);
+        assert_eq!(v.indices(), &[0, 1, 2, 3, 4, 5, 7]);
+        assert_eq!(v.data(), &[0., 1., 2., 3., 4., 5., 8.]);
+        assert!(v.check_structure().is_ok());
+
+        assert_eq!(v.remove(6), None);
+        assert_eq!(v.remove(0), Some(0.));
+        assert_eq!(v.indices(), &[1, 2, 3, 4, 5, 7]);
+
+        v.retain(|ind, val| ind % 2 == 1 && val < 8.);
+        assert_eq!(v.indices(), &[1, 3, 5]);
+        assert_eq!(v.data(), &[1., 3., 5.]);
+
+        v.extend(vec![(6, 1.), (1, 2.), (6, 6.)]);
+        assert_eq!(v.indices(), &[1, 3, 5, 6]);
+        assert_eq!(v.data(), &[2., 3., 5., 6.]);
+        v.extend_combine(vec![(0, 1.), (3, 1.), (0, 1.)], |x, y| x + y);
+        assert_eq!(v.indices(), &[0, 1, 3, 5, 6]);
+        assert_eq!(v.data(), &[2., 2., 4., 5., 6.]);
+
+        let w: CsVec<f64, _, _> = vec![(4, 1.), (2, 2.)].into_iter().collect();
+        assert_eq!(w.dim(), 5);
+        assert_eq!(w.indices(), &[2, 4]);
+        let w = CsVec::new_from_iter(8, w.iter()).unwrap();
+        assert_eq!(w.dim(), 8);
+        assert_eq!(w.data(), &[2., 1.]);
+        let err = CsVec::new_from_iter(4, vec![(4, 1.)]);
+        assert_eq!(err, Err(SprsError::OutOfBoundsIndex));
+    }
+
+    #[test]
+    #[should_panic]
+    fn insert_out_of_bounds() {
+        let mut v = test_vec1();
+        v.insert(8, 1.);
+    }
 }